@@ -0,0 +1,189 @@
+//! Module for preserving the logs, config files and data of processes as test artifacts
+//!
+//! The temporary directories of a process are removed when its handle is dropped. To investigate failed tests,
+//! they can be copied to an artifacts directory first, under a folder named after the test.
+
+use std::path::{Path, PathBuf};
+
+/// Environment variable specifying the artifacts directory when not set in [`crate::LaunchOptions`].
+///
+/// Artifacts are preserved on panic, excluding data directories.
+pub const ARTIFACTS_DIR_ENV: &str = "ZCASH_LOCAL_NET_ARTIFACTS_DIR";
+
+/// When to preserve artifacts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PreserveArtifacts {
+    /// Preserve artifacts if the handle is dropped while the thread is panicking, e.g. a failed test
+    #[default]
+    OnPanic,
+    /// Always preserve artifacts
+    Always,
+}
+
+/// Options for preserving the artifacts of a process when its handle is dropped.
+///
+/// Artifacts are copied to `<dir>/<test name>/<process name>-<pid>/` with `logs`, `config` and `data`
+/// subdirectories.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtifactOptions {
+    /// Artifacts directory
+    pub dir: PathBuf,
+    /// When to preserve artifacts
+    pub preserve: PreserveArtifacts,
+    /// Also preserve the data directory. This may be large.
+    pub include_data_dir: bool,
+    /// Test name, defaults to the name of the thread launching the process if `None`, which is the test path for
+    /// tests run by the default test harness.
+    pub test_name: Option<String>,
+}
+
+impl ArtifactOptions {
+    /// Returns artifact options with the directory specified by [`ARTIFACTS_DIR_ENV`], if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os(ARTIFACTS_DIR_ENV).map(|dir| ArtifactOptions {
+            dir: PathBuf::from(dir),
+            preserve: PreserveArtifacts::default(),
+            include_data_dir: false,
+            test_name: None,
+        })
+    }
+
+    /// Copies the directories of a process to the artifacts directory if required by [`ArtifactOptions::preserve`].
+    ///
+    /// Failures are logged, as this is called from `Drop`.
+    pub(crate) fn preserve(
+        &self,
+        process_name: &str,
+        pid: u32,
+        logs_dir: &Path,
+        config_dir: &Path,
+        data_dir: Option<&Path>,
+    ) {
+        if self.preserve == PreserveArtifacts::OnPanic && !std::thread::panicking() {
+            return;
+        }
+
+        let test_name: String = self
+            .test_name
+            .as_deref()
+            .unwrap_or("unnamed")
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let process_dir = self
+            .dir
+            .join(test_name)
+            .join(format!("{process_name}-{pid}"));
+
+        let mut dirs = vec![("logs", logs_dir), ("config", config_dir)];
+        if let (true, Some(data_dir)) = (self.include_data_dir, data_dir) {
+            dirs.push(("data", data_dir));
+        }
+        for (name, dir) in dirs {
+            if let Err(e) = copy_dir(dir, &process_dir.join(name)) {
+                tracing::error!("failed to preserve {process_name} {name} directory: {e}");
+            }
+        }
+        tracing::info!(
+            "{process_name} artifacts preserved in {}",
+            process_dir.display()
+        );
+    }
+}
+
+/// Recursively copies the contents of `source` to `destination`, skipping anything other than files and
+/// directories, e.g. sockets.
+fn copy_dir(source: &Path, destination: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(destination)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let destination = destination.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &destination)?;
+        } else if file_type.is_file() {
+            std::fs::copy(entry.path(), destination)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LaunchOptions;
+
+    use super::{ArtifactOptions, PreserveArtifacts};
+
+    #[test]
+    fn preserve() {
+        let logs_dir = tempfile::tempdir().unwrap();
+        std::fs::write(logs_dir.path().join("stdout.log"), "done loading").unwrap();
+        let config_dir = tempfile::tempdir().unwrap();
+        std::fs::write(config_dir.path().join("zcash.conf"), "regtest=1").unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(data_dir.path().join("regtest")).unwrap();
+        std::fs::write(data_dir.path().join("regtest").join("debug.log"), "").unwrap();
+        let artifacts_dir = tempfile::tempdir().unwrap();
+
+        let options = ArtifactOptions {
+            dir: artifacts_dir.path().to_path_buf(),
+            preserve: PreserveArtifacts::OnPanic,
+            include_data_dir: true,
+            test_name: None,
+        };
+        // the test name is captured on launch, as handles may be dropped on other threads
+        let options = LaunchOptions {
+            artifacts: Some(options),
+            ..Default::default()
+        }
+        .artifacts()
+        .unwrap();
+        options.preserve(
+            "zcashd",
+            1,
+            logs_dir.path(),
+            config_dir.path(),
+            Some(data_dir.path()),
+        );
+        assert_eq!(std::fs::read_dir(artifacts_dir.path()).unwrap().count(), 0);
+
+        let options = ArtifactOptions {
+            preserve: PreserveArtifacts::Always,
+            ..options
+        };
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                options.preserve(
+                    "zcashd",
+                    1,
+                    logs_dir.path(),
+                    config_dir.path(),
+                    Some(data_dir.path()),
+                )
+            });
+        });
+        let process_dir = artifacts_dir
+            .path()
+            .join("artifacts__tests__preserve")
+            .join("zcashd-1");
+        assert_eq!(
+            std::fs::read_to_string(process_dir.join("logs").join("stdout.log")).unwrap(),
+            "done loading"
+        );
+        assert_eq!(
+            std::fs::read_to_string(process_dir.join("config").join("zcash.conf")).unwrap(),
+            "regtest=1"
+        );
+        assert!(process_dir
+            .join("data")
+            .join("regtest")
+            .join("debug.log")
+            .exists());
+    }
+}
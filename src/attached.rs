@@ -0,0 +1,271 @@
+//! Module for handles attached to processes launched externally, e.g. by another harness or by hand
+//!
+//! [`crate::Zcashd::attach`] and [`crate::Zainod::attach`] return handles implementing [`crate::Validator`] and
+//! [`crate::Indexer`] without launching a process, so the same test code runs against externally managed networks.
+//! The handles do not own the processes, which are left running when they are dropped. For a network described by a
+//! connection manifest, [`crate::manifest::Manifest::validator`] and [`crate::manifest::Manifest::indexers`] return
+//! these handles.
+//!
+//! Example usage:
+//! ```ignore (incomplete)
+//! let zcashd = Zcashd::attach(18232, RpcCredentials::default(), conf_path)?;
+//! zcashd.generate_blocks(1)?;
+//! zcashd.wait_for_height(1, Duration::from_secs(10))?;
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+use getset::Getters;
+use portpicker::Port;
+use tempfile::TempDir;
+
+use crate::{
+    config,
+    error::LaunchError,
+    logs::{self, ProcessLogs},
+    notify::{self, ChainEvent},
+    recording::RpcRecorder,
+    rpc::{RpcClient, RpcCredentials},
+    Indexer, Process, Validator, Zainod, Zcashd, STDERR_LOG, STDOUT_LOG,
+};
+
+/// Zcashd process attached to with [`crate::Zcashd::attach`], which is not stopped when dropped.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct AttachedZcashd {
+    /// RPC Port
+    port: Port,
+    /// RPC credentials
+    credentials: RpcCredentials,
+    /// ZMQ notification port, `None` if Zcashd does not publish notifications
+    zmq_port: Option<Port>,
+    /// Path to config file
+    config_path: PathBuf,
+    /// Path to zcash cli binary
+    zcash_cli_bin: Option<PathBuf>,
+    /// Logs directory, containing empty logs as the process output is not captured
+    logs_dir: TempDir,
+}
+
+impl Zcashd {
+    /// Attaches to a Zcashd process launched externally and returns [`crate::attached::AttachedZcashd`] without
+    /// launching a process.
+    ///
+    /// The RPC server is checked with a `getblockcount` call. `conf_path` is passed to Zcash-cli and read for the ZMQ
+    /// notification port.
+    pub fn attach(
+        rpc_port: Port,
+        credentials: RpcCredentials,
+        conf_path: PathBuf,
+    ) -> Result<AttachedZcashd, LaunchError> {
+        RpcClient::new(rpc_port, credentials.clone())
+            .call("getblockcount", serde_json::json!([]))
+            .map_err(|e| LaunchError::AttachFailed {
+                process_name: Process::Zcashd.to_string(),
+                port: rpc_port,
+                message: e.to_string(),
+            })?;
+        let zmq_port = std::fs::read_to_string(&conf_path)
+            .ok()
+            .and_then(|conf| config::zcashd_zmq_port(&conf));
+
+        Ok(AttachedZcashd {
+            port: rpc_port,
+            credentials,
+            zmq_port,
+            config_path: conf_path,
+            zcash_cli_bin: Zcashd::find_zcash_cli(None),
+            logs_dir: empty_logs_dir(Process::Zcashd)?,
+        })
+    }
+}
+
+impl AttachedZcashd {
+    /// Runs a Zcash-cli command with the given `args`. See [`crate::Zcashd::zcash_cli_command`].
+    pub fn zcash_cli_command(&self, args: &[&str]) -> std::io::Result<std::process::Output> {
+        crate::zcash_cli(self.zcash_cli_bin.as_deref(), &self.config_path, args).output()
+    }
+
+    /// Generate `num_blocks` blocks.
+    pub fn generate_blocks(&self, num_blocks: u32) -> std::io::Result<std::process::Output> {
+        self.zcash_cli_command(&["generate", &num_blocks.to_string()])
+    }
+
+    /// Launches a [`crate::recording::RpcRecorder`] proxy in front of the Zcashd RPC port. See
+    /// [`crate::Zcashd::record_rpc`].
+    pub fn record_rpc(
+        &self,
+        listen_port: Option<Port>,
+        recording_path: PathBuf,
+    ) -> std::io::Result<RpcRecorder> {
+        RpcRecorder::launch(self.port, listen_port, recording_path)
+    }
+
+    /// Subscribes to the block and transaction notifications published by Zcashd over ZMQ. See
+    /// [`crate::Zcashd::subscribe`].
+    ///
+    /// Returns an error if the config file does not enable ZMQ notifications.
+    pub fn subscribe(&self) -> std::io::Result<Receiver<ChainEvent>> {
+        notify::subscribe(self.zmq_port)
+    }
+}
+
+impl Validator for AttachedZcashd {
+    fn rpc_port(&self) -> Port {
+        self.port
+    }
+
+    fn rpc_client(&self) -> RpcClient {
+        RpcClient::new(self.port, self.credentials.clone())
+    }
+}
+
+impl ProcessLogs for AttachedZcashd {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[attached]", Process::Zcashd)
+    }
+}
+
+/// Zainod process attached to with [`crate::Zainod::attach`], which is not stopped when dropped.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct AttachedZainod {
+    /// gRPC Port
+    port: Port,
+    /// Path to config file
+    config_path: PathBuf,
+    /// Logs directory, containing empty logs as the process output is not captured
+    logs_dir: TempDir,
+}
+
+impl Zainod {
+    /// Attaches to a Zainod process launched externally and returns [`crate::attached::AttachedZainod`] without
+    /// launching a process.
+    ///
+    /// The listen port is checked by connecting to it.
+    pub fn attach(listen_port: Port, conf_path: PathBuf) -> Result<AttachedZainod, LaunchError> {
+        std::net::TcpStream::connect(("127.0.0.1", listen_port)).map_err(|e| {
+            LaunchError::AttachFailed {
+                process_name: Process::Zainod.to_string(),
+                port: listen_port,
+                message: e.to_string(),
+            }
+        })?;
+
+        Ok(AttachedZainod {
+            port: listen_port,
+            config_path: conf_path,
+            logs_dir: empty_logs_dir(Process::Zainod)?,
+        })
+    }
+}
+
+impl Indexer for AttachedZainod {
+    fn listen_port(&self) -> Port {
+        self.port
+    }
+}
+
+impl ProcessLogs for AttachedZainod {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[attached]", Process::Zainod)
+    }
+}
+
+/// Returns a logs directory containing every log read through [`crate::logs::ProcessLogs`], all empty.
+fn empty_logs_dir(process: Process) -> Result<TempDir, LaunchError> {
+    let setup_error = |e: std::io::Error| LaunchError::Setup {
+        process_name: process.to_string(),
+        message: format!("failed to create logs directory: {e}"),
+    };
+    let logs_dir = tempfile::tempdir().map_err(setup_error)?;
+    for log in [STDOUT_LOG, STDERR_LOG, logs::TIMESTAMPED_LOG] {
+        std::fs::File::create(logs_dir.path().join(log)).map_err(setup_error)?;
+    }
+    Ok(logs_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::{
+        error::LaunchError, logs::ProcessLogs, mock::MockValidator, rpc::RpcCredentials, Validator,
+        Zainod, Zcashd,
+    };
+
+    #[test]
+    fn attach() {
+        let mock_validator = MockValidator::default();
+        let conf_dir = tempfile::tempdir().unwrap();
+        let conf_path = conf_dir.path().join("zcash.conf");
+        std::fs::write(&conf_path, "zmqpubhashblock=tcp://127.0.0.1:5678\n").unwrap();
+
+        let zcashd = Zcashd::attach(
+            *mock_validator.port(),
+            RpcCredentials::default(),
+            conf_path.clone(),
+        )
+        .unwrap();
+        assert_eq!(zcashd.config_path(), &conf_path);
+        assert_eq!(*zcashd.zmq_port(), Some(5678));
+        assert_eq!(zcashd.process_label(), "zcashd[attached]");
+        assert!(zcashd.stdout().is_empty());
+        mock_validator.chain().generate_blocks(2);
+        zcashd.wait_for_height(2, Duration::from_secs(5)).unwrap();
+        drop(zcashd);
+        // the attached process is left running
+        assert_eq!(
+            mock_validator
+                .rpc_client()
+                .call("getblockcount", json!([]))
+                .unwrap(),
+            json!(2)
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let zainod = Zainod::attach(
+            listener.local_addr().unwrap().port(),
+            conf_dir.path().join("zainod.toml"),
+        )
+        .unwrap();
+        assert_eq!(zainod.process_label(), "zainod[attached]");
+        drop(listener);
+
+        let closed_port = portpicker::pick_unused_port().unwrap();
+        assert!(matches!(
+            Zcashd::attach(closed_port, RpcCredentials::default(), conf_path),
+            Err(LaunchError::AttachFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn attached_timeline() {
+        let mock_validator = MockValidator::default();
+        let conf_dir = tempfile::tempdir().unwrap();
+        let zcashd = Zcashd::attach(
+            *mock_validator.port(),
+            RpcCredentials::default(),
+            conf_dir.path().join("zcash.conf"),
+        )
+        .unwrap();
+        let timeline_dir = tempfile::tempdir().unwrap();
+
+        assert!(zcashd.timestamped_lines().is_empty());
+        let timeline = crate::logs::write_timeline(&[&zcashd], timeline_dir.path()).unwrap();
+        assert!(timeline.is_empty());
+    }
+}
@@ -0,0 +1,606 @@
+//! Command-line tool for running a local regtest network, enabled by the `cli` feature
+//!
+//! `zcash-local-net up` launches Zcashd and Zainod and keeps them running until interrupted, or in the background
+//! with `--daemon`. The ports and directories of the running network are written to a state file, which the `down`,
+//! `status`, `generate` and `logs` subcommands read. `doctor` checks that the local environment can launch the
+//! network.
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use portpicker::Port;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use zcash_local_net::{
+    logs::{LogStream, ProcessLogs},
+    network::ActivationHeights,
+    orphans,
+    rpc::{RpcClient, RpcCredentials},
+    LaunchOptions, Zainod, Zcashd,
+};
+
+/// File name of the state file in the state directory, see [`zcash_local_net::orphans::state_dir`]
+const STATE_FILENAME: &str = "net.json";
+
+/// File name of the output of a daemonized network, next to the state file
+const DAEMON_LOG_FILENAME: &str = "net.log";
+
+/// Time allowed for the network to shut down after `down`
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run a local Zcash regtest network
+#[derive(Parser)]
+#[command(name = "zcash-local-net", version)]
+struct Cli {
+    /// Path to the state file of the network. Defaults to "net.json" in the zcash_local_net state directory
+    #[arg(long, global = true)]
+    state_file: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Launch Zcashd and Zainod and keep them running until interrupted
+    Up(UpArgs),
+    /// Stop the running network
+    Down,
+    /// Print the processes, ports and chain height of the running network
+    Status,
+    /// Generate blocks
+    Generate {
+        /// Number of blocks
+        num_blocks: u32,
+    },
+    /// Print the logs of a process
+    Logs {
+        /// Process
+        process: LogsProcess,
+        /// Print stderr instead of stdout
+        #[arg(long)]
+        stderr: bool,
+        /// Print only the last lines
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+    },
+    /// Check that the binaries, params, ports and temporary directory needed to launch the network are available
+    Doctor {
+        /// Directory containing the Zcash proving parameters
+        #[arg(long)]
+        params_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::Args)]
+struct UpArgs {
+    /// Path to the zcashd binary
+    #[arg(long)]
+    zcashd_bin: Option<PathBuf>,
+    /// Path to the zcash-cli binary
+    #[arg(long)]
+    zcash_cli_bin: Option<PathBuf>,
+    /// Path to the zainod binary
+    #[arg(long)]
+    zainod_bin: Option<PathBuf>,
+    /// Zcashd RPC port. Picked at random if not specified
+    #[arg(long)]
+    rpc_port: Option<Port>,
+    /// Zainod listen port. Picked at random if not specified
+    #[arg(long)]
+    indexer_port: Option<Port>,
+    /// Address receiving the block rewards of generated blocks
+    #[arg(long)]
+    miner_address: Option<String>,
+    /// Directory containing the Zcash proving parameters
+    #[arg(long)]
+    params_dir: Option<PathBuf>,
+    /// Write a JSON connection manifest describing the processes for other tools
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// Launch Zcashd only
+    #[arg(long)]
+    no_indexer: bool,
+    /// Keep the network running in the background and return once it is ready
+    #[arg(long)]
+    daemon: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogsProcess {
+    Zcashd,
+    Zainod,
+}
+
+/// Running network, written to the state file once it is ready
+#[derive(Serialize, Deserialize)]
+struct NetState {
+    /// Pid of the `up` process
+    pid: u32,
+    /// Start time of the `up` process, see [`zcash_local_net::orphans::start_time`]
+    start_time: Option<u64>,
+    /// Zcashd process
+    zcashd: ProcessState,
+    /// Zcashd ZMQ notification port
+    zmq_port: Port,
+    /// Zainod process
+    zainod: Option<ProcessState>,
+}
+
+impl NetState {
+    /// Returns whether the `up` process is running, and not another process reusing its pid.
+    fn is_running(&self) -> bool {
+        orphans::is_running(self.pid, self.start_time)
+    }
+
+    /// Returns whether the `up` process or any process of the network is running.
+    fn any_running(&self) -> bool {
+        self.is_running()
+            || self.zcashd.is_running()
+            || self.zainod.as_ref().is_some_and(ProcessState::is_running)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProcessState {
+    pid: u32,
+    start_time: Option<u64>,
+    port: Port,
+    config_path: PathBuf,
+    logs_dir: PathBuf,
+}
+
+impl ProcessState {
+    fn is_running(&self) -> bool {
+        orphans::is_running(self.pid, self.start_time)
+    }
+
+    fn logs(&self, name: &str) -> StateLogs {
+        StateLogs {
+            logs_dir: self.logs_dir.clone(),
+            label: format!("{name}[{}]", self.pid),
+        }
+    }
+}
+
+/// Logs of a process of the running network
+struct StateLogs {
+    logs_dir: PathBuf,
+    label: String,
+}
+
+impl ProcessLogs for StateLogs {
+    fn logs_path(&self) -> &Path {
+        &self.logs_dir
+    }
+
+    fn process_label(&self) -> String {
+        self.label.clone()
+    }
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    let state_file = cli
+        .state_file
+        .unwrap_or_else(|| orphans::state_dir().join(STATE_FILENAME));
+    let result = match cli.command {
+        Command::Up(args) if args.daemon => daemonize(&state_file),
+        Command::Up(args) => up(args, &state_file),
+        Command::Down => down(&state_file),
+        Command::Status => status(&state_file),
+        Command::Generate { num_blocks } => generate(&state_file, num_blocks),
+        Command::Logs {
+            process,
+            stderr,
+            lines,
+        } => logs(&state_file, process, stderr, lines),
+        Command::Doctor { params_dir } => doctor(params_dir.as_deref()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn up(args: UpArgs, state_file: &Path) -> Result<(), String> {
+    if let Some(state) = read_state(state_file)? {
+        if state.is_running() {
+            return Err(format!(
+                "network is already running with pid {}, see {}",
+                state.pid,
+                state_file.display()
+            ));
+        }
+    }
+    if let Err(e) = orphans::cleanup_stale() {
+        tracing::warn!("failed to clean up stale processes: {e}");
+    }
+
+    let options = LaunchOptions {
+        params_dir: args.params_dir,
+        manifest: args.manifest,
+        zmq_notifications: true,
+        ..LaunchOptions::default()
+    };
+    let zcashd = Zcashd::launch_with_options(
+        args.zcashd_bin,
+        args.zcash_cli_bin,
+        args.rpc_port,
+        &ActivationHeights::default(),
+        args.miner_address.as_deref(),
+        &options,
+    )
+    .map_err(|e| e.to_string())?;
+    let zainod = if args.no_indexer {
+        None
+    } else {
+        Some(
+            Zainod::launch_with_options(
+                args.zainod_bin,
+                args.indexer_port,
+                *zcashd.port(),
+                &options,
+            )
+            .map_err(|e| e.to_string())?,
+        )
+    };
+
+    let state = NetState {
+        pid: std::process::id(),
+        start_time: orphans::start_time(std::process::id()),
+        zcashd: ProcessState {
+            pid: zcashd.handle().id(),
+            start_time: orphans::start_time(zcashd.handle().id()),
+            port: *zcashd.port(),
+            config_path: zcashd.config_path(),
+            logs_dir: zcashd.logs_path().to_path_buf(),
+        },
+        zmq_port: zcashd
+            .zmq_port()
+            .expect("zcashd should be launched with ZMQ notifications"),
+        zainod: zainod.as_ref().map(|zainod| ProcessState {
+            pid: zainod.handle().id(),
+            start_time: orphans::start_time(zainod.handle().id()),
+            port: *zainod.port(),
+            config_path: zainod.config_path(),
+            logs_dir: zainod.logs_path().to_path_buf(),
+        }),
+    };
+    write_state(state_file, &state)?;
+    print_state(&state);
+    println!("Press Ctrl-C or run `zcash-local-net down` to stop the network.");
+
+    let signal = wait_for_shutdown_signal();
+    println!("Stopping the network...");
+    drop(zainod);
+    drop(zcashd);
+    if let Err(e) = std::fs::remove_file(state_file) {
+        tracing::warn!("failed to remove {}: {e}", state_file.display());
+    }
+    signal
+}
+
+/// Runs `up` in the background with the same arguments and returns once the network is ready.
+#[cfg(unix)]
+fn daemonize(state_file: &Path) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let state_dir = state_file.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(state_dir)
+        .map_err(|e| format!("failed to create {}: {e}", state_dir.display()))?;
+    let log_path = state_dir.join(DAEMON_LOG_FILENAME);
+    let log = std::fs::File::create(&log_path)
+        .map_err(|e| format!("failed to create {}: {e}", log_path.display()))?;
+    let executable =
+        std::env::current_exe().map_err(|e| format!("failed to locate executable: {e}"))?;
+
+    let mut command = std::process::Command::new(executable);
+    command
+        .args(std::env::args_os().skip(1).filter(|arg| arg != "--daemon"))
+        .stdin(std::process::Stdio::null())
+        .stdout(log.try_clone().map_err(|e| e.to_string())?)
+        .stderr(log);
+    // SAFETY: setsid is async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("failed to launch the network in the background: {e}"))?;
+
+    loop {
+        if let Some(state) = read_state(state_file)? {
+            if state.pid == child.id() {
+                print_state(&state);
+                println!(
+                    "Output is logged to {}. Run `zcash-local-net down` to stop the network.",
+                    log_path.display()
+                );
+                return Ok(());
+            }
+        }
+        if let Some(exit_status) = child.try_wait().map_err(|e| e.to_string())? {
+            let output = std::fs::read_to_string(&log_path).unwrap_or_default();
+            return Err(format!(
+                "network failed to launch. Exit status: {exit_status}\n{output}"
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[cfg(not(unix))]
+fn daemonize(_state_file: &Path) -> Result<(), String> {
+    Err("--daemon is only supported on unix".to_string())
+}
+
+fn down(state_file: &Path) -> Result<(), String> {
+    let state = require_state(state_file)?;
+    if !state.is_running() {
+        println!("Network is not running, removing stale state.");
+        let _ = std::fs::remove_file(state_file);
+        orphans::cleanup_stale().map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    terminate(state.pid)?;
+    let start = std::time::Instant::now();
+    while state.any_running() {
+        if start.elapsed() > SHUTDOWN_TIMEOUT {
+            return Err(format!(
+                "network with pid {} did not stop within {SHUTDOWN_TIMEOUT:?}",
+                state.pid
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    println!("Network stopped.");
+    Ok(())
+}
+
+fn status(state_file: &Path) -> Result<(), String> {
+    let state = require_running(state_file)?;
+    print_state(&state);
+    match rpc_client(&state).call("getblockcount", json!([])) {
+        Ok(height) => println!("Chain height: {height}"),
+        Err(e) => println!("Chain height: unavailable ({e})"),
+    }
+    Ok(())
+}
+
+fn generate(state_file: &Path, num_blocks: u32) -> Result<(), String> {
+    let state = require_running(state_file)?;
+    let block_hashes = rpc_client(&state)
+        .call("generate", json!([num_blocks]))
+        .map_err(|e| e.to_string())?;
+    for block_hash in block_hashes.as_array().into_iter().flatten() {
+        println!("{}", block_hash.as_str().unwrap_or_default());
+    }
+    Ok(())
+}
+
+fn logs(
+    state_file: &Path,
+    process: LogsProcess,
+    stderr: bool,
+    lines: Option<usize>,
+) -> Result<(), String> {
+    let state = require_state(state_file)?;
+    let logs = match process {
+        LogsProcess::Zcashd => state.zcashd.logs("zcashd"),
+        LogsProcess::Zainod => state
+            .zainod
+            .as_ref()
+            .ok_or("network was launched without zainod")?
+            .logs("zainod"),
+    };
+    let stream = if stderr {
+        LogStream::Stderr
+    } else {
+        LogStream::Stdout
+    };
+    if !logs.log_path(stream).exists() {
+        return Err(format!(
+            "{} does not exist",
+            logs.log_path(stream).display()
+        ));
+    }
+
+    match lines {
+        Some(lines) => {
+            for line in logs.tail(stream, lines) {
+                println!("{line}");
+            }
+        }
+        None => print!("{}", logs.log(stream)),
+    }
+    Ok(())
+}
+
+fn doctor(params_dir: Option<&Path>) -> Result<(), String> {
+    let report = zcash_local_net::doctor::check(params_dir);
+    println!("{report}");
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err("environment check failed".to_string())
+    }
+}
+
+fn rpc_client(state: &NetState) -> RpcClient {
+    RpcClient::new(state.zcashd.port, RpcCredentials::default())
+}
+
+fn print_state(state: &NetState) {
+    println!("Network running with pid {}", state.pid);
+    println!(
+        "  zcashd[{}]: RPC port {}, ZMQ port {}\n    config: {}\n    logs: {}",
+        state.zcashd.pid,
+        state.zcashd.port,
+        state.zmq_port,
+        state.zcashd.config_path.display(),
+        state.zcashd.logs_dir.display()
+    );
+    if let Some(zainod) = &state.zainod {
+        println!(
+            "  zainod[{}]: listen port {}\n    config: {}\n    logs: {}",
+            zainod.pid,
+            zainod.port,
+            zainod.config_path.display(),
+            zainod.logs_dir.display()
+        );
+    }
+}
+
+fn read_state(state_file: &Path) -> Result<Option<NetState>, String> {
+    match std::fs::read_to_string(state_file) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("invalid state file {}: {e}", state_file.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("failed to read {}: {e}", state_file.display())),
+    }
+}
+
+fn require_state(state_file: &Path) -> Result<NetState, String> {
+    read_state(state_file)?.ok_or_else(|| {
+        format!(
+            "no network found at {}. Run `zcash-local-net up` first",
+            state_file.display()
+        )
+    })
+}
+
+fn require_running(state_file: &Path) -> Result<NetState, String> {
+    let state = require_state(state_file)?;
+    if !state.is_running() {
+        return Err(format!(
+            "network with pid {} is not running. Run `zcash-local-net down` to clean up",
+            state.pid
+        ));
+    }
+    Ok(state)
+}
+
+/// Writes the state file through a temporary file, so it is never read partially written.
+fn write_state(state_file: &Path, state: &NetState) -> Result<(), String> {
+    if let Some(state_dir) = state_file.parent() {
+        std::fs::create_dir_all(state_dir)
+            .map_err(|e| format!("failed to create {}: {e}", state_dir.display()))?;
+    }
+    let contents = serde_json::to_string_pretty(state).expect("state should serialize");
+    let temp_path = state_file.with_extension("json.tmp");
+    std::fs::write(&temp_path, contents)
+        .and_then(|()| std::fs::rename(&temp_path, state_file))
+        .map_err(|e| format!("failed to write {}: {e}", state_file.display()))
+}
+
+/// Blocks until SIGINT or, on unix, SIGTERM is received.
+fn wait_for_shutdown_signal() -> Result<(), String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    runtime.block_on(async {
+        #[cfg(unix)]
+        {
+            let mut terminate =
+                tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                    .map_err(|e| e.to_string())?;
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => result.map_err(|e| e.to_string()),
+                _ = terminate.recv() => Ok(()),
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::signal::ctrl_c().await.map_err(|e| e.to_string())
+    })
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<(), String> {
+    // SAFETY: sending a signal has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(format!(
+            "failed to stop network with pid {pid}: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate(pid: u32) -> Result<(), String> {
+    Err(format!(
+        "stopping the network is only supported on unix, stop process {pid} manually"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::CommandFactory;
+
+    use super::{Cli, NetState, ProcessState};
+
+    #[test]
+    fn cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn stale_state() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let state_file = state_dir.path().join(super::STATE_FILENAME);
+        assert!(super::read_state(&state_file).unwrap().is_none());
+        assert!(super::down(&state_file)
+            .unwrap_err()
+            .contains("no network found"));
+
+        let state = NetState {
+            pid: i32::MAX as u32,
+            start_time: Some(1),
+            zcashd: ProcessState {
+                pid: i32::MAX as u32,
+                start_time: None,
+                port: 18232,
+                config_path: PathBuf::from("zcash.conf"),
+                logs_dir: PathBuf::from("logs"),
+            },
+            zmq_port: 28332,
+            zainod: None,
+        };
+        super::write_state(&state_file, &state).unwrap();
+        let read = super::read_state(&state_file).unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&read).unwrap(),
+            serde_json::to_value(&state).unwrap()
+        );
+
+        assert!(super::generate(&state_file, 1)
+            .unwrap_err()
+            .contains("is not running"));
+        assert!(super::status(&state_file)
+            .unwrap_err()
+            .contains("is not running"));
+        super::down(&state_file).unwrap();
+        assert!(!state_file.exists());
+    }
+}
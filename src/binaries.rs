@@ -0,0 +1,539 @@
+//! Module for locating process binaries and checking their versions
+//!
+//! Binaries are resolved from an explicit path, then an environment variable such as `ZCASHD_BIN`, then `$PATH`.
+//! The version reported by the binary is checked against the range supported by this crate before launching.
+//!
+//! Several versions of each binary can be kept in a [`BinariesDir`] to test against each of them, see
+//! [`crate::matrix`].
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use regex::Regex;
+
+use crate::{error::LaunchError, wait::Backoff};
+
+/// Environment variable specifying the [`BinariesDir`] used by [`BinariesDir::from_env`]
+pub const BINARIES_DIR_ENV: &str = "ZCASH_LOCAL_NET_BINARIES_DIR";
+
+/// Time allowed for a binary to print its version
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Version outputs of the binaries run by this process, identified by path and modification time, as running a binary
+/// may take seconds
+static VERSION_OUTPUTS: Mutex<Vec<(PathBuf, SystemTime, Option<String>)>> = Mutex::new(Vec::new());
+
+/// Binaries which can be resolved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binary {
+    /// Zcashd validator
+    Zcashd,
+    /// Zcash-cli RPC client for Zcashd
+    ZcashCli,
+    /// Zebrad validator
+    Zebrad,
+    /// Zainod indexer
+    Zainod,
+    /// Lightwalletd indexer
+    Lightwalletd,
+}
+
+impl std::fmt::Display for Binary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Binary {
+    /// Returns the binary name looked up in $PATH.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Zcashd => "zcashd",
+            Self::ZcashCli => "zcash-cli",
+            Self::Zebrad => "zebrad",
+            Self::Zainod => "zainod",
+            Self::Lightwalletd => "lightwalletd",
+        }
+    }
+
+    /// Returns the environment variable specifying the path to the binary.
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Self::Zcashd => "ZCASHD_BIN",
+            Self::ZcashCli => "ZCASH_CLI_BIN",
+            Self::Zebrad => "ZEBRAD_BIN",
+            Self::Zainod => "ZAINOD_BIN",
+            Self::Lightwalletd => "LIGHTWALLETD_BIN",
+        }
+    }
+
+    /// Returns the range of versions supported by this crate.
+    pub fn supported_versions(&self) -> VersionRange {
+        let (min, max) = match self {
+            Self::Zcashd | Self::ZcashCli => (Version::new(5, 6, 0), Version::new(7, 0, 0)),
+            Self::Zebrad => (Version::new(1, 0, 0), Version::new(3, 0, 0)),
+            Self::Zainod => (Version::new(0, 1, 0), Version::new(0, 2, 0)),
+            Self::Lightwalletd => (Version::new(0, 4, 9), Version::new(0, 5, 0)),
+        };
+        VersionRange { min, max }
+    }
+
+    /// Returns the binary whose release includes this binary, e.g. zcash-cli is released with zcashd.
+    pub fn release(&self) -> Binary {
+        match self {
+            Self::ZcashCli => Self::Zcashd,
+            _ => *self,
+        }
+    }
+
+    /// Returns the arguments making the binary print its version and exit.
+    fn version_args(&self) -> &'static [&'static str] {
+        match self {
+            Self::Lightwalletd => &["version"],
+            _ => &["--version"],
+        }
+    }
+}
+
+/// Version of a binary, ignoring pre-release and build suffixes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    /// Major version
+    pub major: u64,
+    /// Minor version
+    pub minor: u64,
+    /// Patch version
+    pub patch: u64,
+}
+
+impl Version {
+    /// Creates a version.
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses the first `major.minor.patch` version in `output`, e.g. "Zcash Daemon version v6.1.0".
+    pub fn parse(output: &str) -> Option<Self> {
+        let regex = Regex::new(r"(\d+)\.(\d+)\.(\d+)").expect("should be a valid regex");
+        let captures = regex.captures(output)?;
+        let part = |index: usize| captures[index].parse().ok();
+        Some(Version::new(part(1)?, part(2)?, part(3)?))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Range of supported versions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VersionRange {
+    /// Oldest supported version. Older versions are rejected.
+    pub min: Version,
+    /// First version which is not yet supported. Newer versions are launched with a warning.
+    pub max: Version,
+}
+
+impl std::fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, ">={}, <{}", self.min, self.max)
+    }
+}
+
+/// Where a binary was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinarySource {
+    /// Path specified by the caller
+    Explicit,
+    /// Path specified by the binary's environment variable, see [`Binary::env_var`]
+    Env,
+    /// Found in $PATH
+    Path,
+}
+
+/// Binary located by [`resolve`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedBinary {
+    /// Binary
+    pub binary: Binary,
+    /// Path to the binary
+    pub path: PathBuf,
+    /// Where the binary was found
+    pub source: BinarySource,
+    /// Version reported by the binary, if it could be parsed
+    pub version: Option<Version>,
+}
+
+/// Locates `binary` without running it.
+///
+/// Checks `explicit_path`, then the binary's environment variable, then $PATH. A path without a directory, e.g.
+/// "zcashd", is looked up in $PATH.
+pub fn find(
+    binary: Binary,
+    explicit_path: Option<&Path>,
+) -> Result<(PathBuf, BinarySource), LaunchError> {
+    find_with_env(binary, explicit_path, |key| std::env::var_os(key))
+}
+
+/// Locates `binary` like [`find`], reading environment variables with `env_var`.
+fn find_with_env(
+    binary: Binary,
+    explicit_path: Option<&Path>,
+    env_var: impl Fn(&str) -> Option<OsString>,
+) -> Result<(PathBuf, BinarySource), LaunchError> {
+    let (path, source) = match explicit_path {
+        Some(path) => (path.to_path_buf(), BinarySource::Explicit),
+        None => match env_var(binary.env_var()) {
+            Some(path) => (PathBuf::from(path), BinarySource::Env),
+            None => (PathBuf::from(binary.name()), BinarySource::Path),
+        },
+    };
+
+    let found = if path.components().count() == 1 {
+        search_path(&path)
+    } else {
+        is_executable(&path).then_some(path.clone())
+    };
+    found
+        .map(|found| (found, source))
+        .ok_or_else(|| LaunchError::BinaryNotFound {
+            binary: binary.to_string(),
+            path: path.display().to_string(),
+            env_var: binary.env_var().to_string(),
+        })
+}
+
+/// Locates `binary` and checks its version.
+///
+/// Versions older than [`Binary::supported_versions`] are rejected with [`LaunchError::UnsupportedVersion`]. Newer
+/// versions and versions which could not be determined are logged as warnings. Set `skip_version_check` to only
+/// locate the binary.
+pub fn resolve(
+    binary: Binary,
+    explicit_path: Option<&Path>,
+    skip_version_check: bool,
+) -> Result<ResolvedBinary, LaunchError> {
+    let (path, source) = find(binary, explicit_path)?;
+    let mut resolved = ResolvedBinary {
+        binary,
+        path,
+        source,
+        version: None,
+    };
+    if skip_version_check {
+        return Ok(resolved);
+    }
+
+    resolved.version =
+        version_output(binary, &resolved.path).and_then(|output| Version::parse(&output));
+    let supported = binary.supported_versions();
+    match resolved.version {
+        Some(version) if version < supported.min => {
+            return Err(LaunchError::UnsupportedVersion {
+                binary: binary.to_string(),
+                path: resolved.path.display().to_string(),
+                version: version.to_string(),
+                supported: supported.to_string(),
+            });
+        }
+        Some(version) if version >= supported.max => tracing::warn!(
+            "{binary} {version} at {} is newer than the supported versions {supported}",
+            resolved.path.display()
+        ),
+        Some(_) => (),
+        None => tracing::warn!(
+            "could not determine the version of {binary} at {}",
+            resolved.path.display()
+        ),
+    }
+    Ok(resolved)
+}
+
+/// Directory of binaries laid out by name and version.
+///
+/// Each version of a binary is in `<dir>/<release name>/<version>/<binary name>`, where the release name is the name
+/// of [`Binary::release`]. For example:
+/// ```text
+/// binaries/
+/// ├── zcashd/
+/// │   ├── 5.10.0/
+/// │   │   ├── zcashd
+/// │   │   └── zcash-cli
+/// │   └── 6.1.0/
+/// │       ├── zcashd
+/// │       └── zcash-cli
+/// └── zainod/
+///     └── 0.1.2/
+///         └── zainod
+/// ```
+/// Version directories may be prefixed with "v". Directories which are not versions are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinariesDir {
+    path: PathBuf,
+}
+
+impl BinariesDir {
+    /// Creates a binaries directory at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        BinariesDir { path: path.into() }
+    }
+
+    /// Returns the binaries directory specified by [`BINARIES_DIR_ENV`], if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var_os(BINARIES_DIR_ENV).map(BinariesDir::new)
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the available versions of `binary`, oldest first.
+    ///
+    /// Returns an empty list if the directory has no versions of `binary`.
+    pub fn versions(&self, binary: Binary) -> std::io::Result<Vec<VersionedBinary>> {
+        let release_dir = self.path.join(binary.release().name());
+        let entries = match std::fs::read_dir(&release_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut versions = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let Some(version) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| Version::parse(name.trim_start_matches('v')))
+            else {
+                continue;
+            };
+            let path = entry.path().join(binary.name());
+            if is_executable(&path) {
+                versions.push(VersionedBinary {
+                    binary,
+                    version,
+                    path,
+                });
+            }
+        }
+        versions.sort_by_key(|versioned| versioned.version);
+        Ok(versions)
+    }
+}
+
+/// Version of a binary in a [`BinariesDir`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionedBinary {
+    /// Binary
+    pub binary: Binary,
+    /// Version, taken from the directory name
+    pub version: Version,
+    /// Path to the binary
+    pub path: PathBuf,
+}
+
+impl VersionedBinary {
+    /// Returns the path to another binary of the same release, e.g. zcash-cli for zcashd, if it exists.
+    pub fn companion(&self, binary: Binary) -> Option<PathBuf> {
+        let path = self.path.parent()?.join(binary.name());
+        is_executable(&path).then_some(path)
+    }
+}
+
+impl std::fmt::Display for VersionedBinary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.binary, self.version)
+    }
+}
+
+/// Runs the binary's version command and returns its stdout and stderr, or `None` if it failed or timed out.
+///
+/// The output is cached until the binary is modified.
+fn version_output(binary: Binary, path: &Path) -> Option<String> {
+    let Ok(modified) = path.metadata().and_then(|metadata| metadata.modified()) else {
+        return run_version_command(binary, path);
+    };
+    let cached = VERSION_OUTPUTS
+        .lock()
+        .expect("version outputs lock should not be poisoned")
+        .iter()
+        .find(|(cached_path, cached_modified, _)| {
+            cached_path == path && *cached_modified == modified
+        })
+        .map(|(_, _, output)| output.clone());
+    if let Some(output) = cached {
+        return output;
+    }
+
+    let output = run_version_command(binary, path);
+    VERSION_OUTPUTS
+        .lock()
+        .expect("version outputs lock should not be poisoned")
+        .push((path.to_path_buf(), modified, output.clone()));
+    output
+}
+
+fn run_version_command(binary: Binary, path: &Path) -> Option<String> {
+    let mut child = Command::new(path)
+        .args(binary.version_args())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    // some binaries start normally when given an unknown flag
+    let mut backoff = Backoff::new(VERSION_TIMEOUT);
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if backoff.wait() => (),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    let output = child.wait_with_output().ok()?;
+    Some(format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+/// Returns the first executable named `name` in $PATH.
+fn search_path(name: &Path) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+// the tests write executable scripts
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::fs::PermissionsExt, path::Path};
+
+    use crate::error::LaunchError;
+
+    use super::{BinariesDir, Binary, BinarySource, Version};
+
+    fn write_script(dir: &Path, name: &str, output: &str) -> std::path::PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\necho '{output}'\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn resolve() {
+        assert_eq!(
+            Version::parse("Zcash Daemon version v6.1.0-rc1"),
+            Some(Version::new(6, 1, 0))
+        );
+        assert_eq!(Version::parse("zainod"), None);
+
+        let bin_dir = tempfile::tempdir().unwrap();
+        let supported = write_script(bin_dir.path(), "zcashd", "Zcash Daemon version v6.1.0");
+        let resolved = super::resolve(Binary::Zcashd, Some(&supported), false).unwrap();
+        assert_eq!(resolved.source, BinarySource::Explicit);
+        assert_eq!(resolved.version, Some(Version::new(6, 1, 0)));
+
+        let old = write_script(bin_dir.path(), "old-zcashd", "Zcash Daemon version v5.0.0");
+        assert!(matches!(
+            super::resolve(Binary::Zcashd, Some(&old), false),
+            Err(LaunchError::UnsupportedVersion { .. })
+        ));
+        assert!(super::resolve(Binary::Zcashd, Some(&old), true).is_ok());
+
+        let newer = write_script(bin_dir.path(), "new-zcashd", "Zcash Daemon version v7.0.0");
+        assert!(super::resolve(Binary::Zcashd, Some(&newer), false).is_ok());
+
+        // the version is cached until the binary is modified
+        let replaced = write_script(
+            bin_dir.path(),
+            "replaced-zcashd",
+            "Zcash Daemon version v6.1.0",
+        );
+        assert!(super::resolve(Binary::Zcashd, Some(&replaced), false).is_ok());
+        let modified = replaced.metadata().unwrap().modified().unwrap();
+        write_script(
+            bin_dir.path(),
+            "replaced-zcashd",
+            "Zcash Daemon version v5.0.0",
+        );
+        let set_modified = |modified| {
+            // the file is closed before running it, which fails while it is open for writing
+            let file = std::fs::File::options()
+                .write(true)
+                .open(&replaced)
+                .unwrap();
+            file.set_modified(modified).unwrap();
+        };
+        set_modified(modified);
+        assert!(super::resolve(Binary::Zcashd, Some(&replaced), false).is_ok());
+        set_modified(modified + std::time::Duration::from_secs(1));
+        assert!(matches!(
+            super::resolve(Binary::Zcashd, Some(&replaced), false),
+            Err(LaunchError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn binaries_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let zcashd_dir = dir.path().join("zcashd");
+        write_script(&zcashd_dir.join("6.1.0"), "zcashd", "");
+        write_script(&zcashd_dir.join("6.1.0"), "zcash-cli", "");
+        write_script(&zcashd_dir.join("v5.10.0"), "zcashd", "");
+        write_script(&zcashd_dir.join("nightly"), "zcashd", "");
+        std::fs::create_dir_all(zcashd_dir.join("6.2.0")).unwrap();
+
+        let binaries_dir = BinariesDir::new(dir.path());
+        let versions = binaries_dir.versions(Binary::Zcashd).unwrap();
+        assert_eq!(
+            versions
+                .iter()
+                .map(|versioned| versioned.version)
+                .collect::<Vec<_>>(),
+            vec![Version::new(5, 10, 0), Version::new(6, 1, 0)]
+        );
+        assert_eq!(versions[0].companion(Binary::ZcashCli), None);
+        assert_eq!(
+            versions[1].companion(Binary::ZcashCli),
+            Some(zcashd_dir.join("6.1.0").join("zcash-cli"))
+        );
+        assert_eq!(binaries_dir.versions(Binary::ZcashCli).unwrap().len(), 1);
+        assert!(binaries_dir.versions(Binary::Zainod).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Module for capturing the stdout and stderr of child processes
+//!
+//! A reader thread per stream splits the output into lines and sends each line to every subscriber over a channel,
+//! so subscribers such as the log file writer and readiness matcher see lines as soon as they are written.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use crate::logs::{self, LogStream};
+
+/// Line captured from the output of a child process
+#[derive(Clone, Debug)]
+pub(crate) struct CapturedLine {
+    /// Stream the line was read from
+    pub(crate) stream: LogStream,
+    /// Time the line was read
+    pub(crate) time: SystemTime,
+    /// Line without the trailing newline
+    pub(crate) line: Arc<str>,
+}
+
+/// Event sent to log subscribers
+#[derive(Clone, Debug)]
+pub(crate) enum LogEvent {
+    /// Line read from a stream
+    Line(CapturedLine),
+    /// A stream reached end of file, e.g. the process exited
+    Closed,
+}
+
+/// Sender of a log subscriber
+enum Subscriber {
+    Blocking(Sender<LogEvent>),
+    #[cfg(feature = "async")]
+    Async(tokio::sync::mpsc::UnboundedSender<LogEvent>),
+}
+
+impl Subscriber {
+    /// Sends the event, returning `false` if the receiver was dropped.
+    fn send(&self, event: LogEvent) -> bool {
+        match self {
+            Subscriber::Blocking(sender) => sender.send(event).is_ok(),
+            #[cfg(feature = "async")]
+            Subscriber::Async(sender) => sender.send(event).is_ok(),
+        }
+    }
+}
+
+type Subscribers = Arc<Mutex<Vec<Subscriber>>>;
+
+/// Captures the output streams of a child process and sends each line to the subscribers.
+///
+/// Subscribers are removed once their receiver is dropped.
+pub(crate) struct LogCapture {
+    subscribers: Subscribers,
+}
+
+impl LogCapture {
+    /// Creates a capture with subscribers writing the stdout, stderr and timestamped logs to `logs_dir`.
+    ///
+    /// If `span` is specified, a subscriber also emits each line as a `tracing` event inside the span.
+    pub(crate) fn new(logs_dir: &Path, span: Option<tracing::Span>) -> std::io::Result<Self> {
+        let capture = LogCapture {
+            subscribers: Subscribers::default(),
+        };
+
+        let mut stdout_log = File::create(logs_dir.join(crate::STDOUT_LOG))?;
+        let mut stderr_log = File::create(logs_dir.join(crate::STDERR_LOG))?;
+        let mut timestamped_log = File::create(logs_dir.join(logs::TIMESTAMPED_LOG))?;
+        let events = capture.subscribe();
+        std::thread::spawn(move || {
+            for event in events {
+                let LogEvent::Line(line) = event else {
+                    continue;
+                };
+                let log = match line.stream {
+                    LogStream::Stdout => &mut stdout_log,
+                    LogStream::Stderr => &mut stderr_log,
+                };
+                log.write_all(format!("{}\n", line.line).as_bytes())
+                    .unwrap_or_else(|e| panic!("should be able to write {} log: {e}", line.stream));
+                logs::write_timestamped(&mut timestamped_log, line.time, line.stream, &line.line)
+                    .expect("should be able to write timestamped log");
+            }
+        });
+
+        if let Some(span) = span {
+            let events = capture.subscribe();
+            std::thread::spawn(move || {
+                for event in events {
+                    if let LogEvent::Line(line) = event {
+                        span.in_scope(|| tracing::info!(stream = %line.stream, "{}", line.line));
+                    }
+                }
+            });
+        }
+
+        Ok(capture)
+    }
+
+    /// Returns a receiver of the lines captured after this call.
+    pub(crate) fn subscribe(&self) -> Receiver<LogEvent> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.add_subscriber(Subscriber::Blocking(sender));
+        receiver
+    }
+
+    /// Returns an async receiver of the lines captured after this call.
+    #[cfg(feature = "async")]
+    pub(crate) fn subscribe_async(&self) -> tokio::sync::mpsc::UnboundedReceiver<LogEvent> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.add_subscriber(Subscriber::Async(sender));
+        receiver
+    }
+
+    fn add_subscriber(&self, subscriber: Subscriber) {
+        self.subscribers
+            .lock()
+            .expect("subscribers lock should not be poisoned")
+            .push(subscriber);
+    }
+
+    /// Reads `output` line by line on a new thread until end of file.
+    pub(crate) fn capture(&self, stream: LogStream, output: impl Read + Send + 'static) {
+        let subscribers = self.subscribers.clone();
+        std::thread::spawn(move || {
+            let mut output = BufReader::new(output);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match output.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(e) => {
+                        tracing::error!("failed to read {stream}: {e}");
+                        break;
+                    }
+                }
+                send(&subscribers, captured_line(stream, &line));
+            }
+            send(&subscribers, LogEvent::Closed);
+        });
+    }
+
+    /// Reads `output` line by line on a new task until end of file.
+    #[cfg(feature = "async")]
+    pub(crate) fn capture_async(
+        &self,
+        stream: LogStream,
+        output: impl tokio::io::AsyncRead + Unpin + Send + 'static,
+    ) {
+        use tokio::io::AsyncBufReadExt;
+
+        let subscribers = self.subscribers.clone();
+        tokio::spawn(async move {
+            let mut output = tokio::io::BufReader::new(output);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match output.read_until(b'\n', &mut line).await {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(e) => {
+                        tracing::error!("failed to read {stream}: {e}");
+                        break;
+                    }
+                }
+                send(&subscribers, captured_line(stream, &line));
+            }
+            send(&subscribers, LogEvent::Closed);
+        });
+    }
+}
+
+fn captured_line(stream: LogStream, line: &[u8]) -> LogEvent {
+    let time = SystemTime::now();
+    let line = String::from_utf8_lossy(line);
+    LogEvent::Line(CapturedLine {
+        stream,
+        time,
+        line: line.trim_end_matches(['\n', '\r']).into(),
+    })
+}
+
+fn send(subscribers: &Subscribers, event: LogEvent) {
+    subscribers
+        .lock()
+        .expect("subscribers lock should not be poisoned")
+        .retain(|subscriber| subscriber.send(event.clone()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::logs::LogStream;
+
+    use super::{LogCapture, LogEvent};
+
+    #[test]
+    fn capture() {
+        let logs_dir = tempfile::tempdir().unwrap();
+        let capture = LogCapture::new(logs_dir.path(), None).unwrap();
+        let events = capture.subscribe();
+        capture.capture(LogStream::Stdout, &b"starting\nready\n"[..]);
+
+        let mut lines = Vec::new();
+        while let LogEvent::Line(line) = events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            assert_eq!(line.stream, LogStream::Stdout);
+            lines.push(line.line.to_string());
+        }
+        assert_eq!(lines, vec!["starting", "ready"]);
+
+        // the file writer runs on its own thread, so it may lag behind other subscribers
+        drop(capture);
+        let stdout_log = logs_dir.path().join(crate::STDOUT_LOG);
+        let mut backoff = crate::wait::Backoff::new(Duration::from_secs(5));
+        while std::fs::read_to_string(&stdout_log).unwrap() != "starting\nready\n" {
+            assert!(backoff.wait(), "stdout log was not written");
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Client for the light wallet `CompactTxStreamer` gRPC service served by indexers
+//!
+//! The compact block and service types are generated from the lightwalletd protocol definitions, the same as those
+//! of `zcash_client_backend::proto`.
+
+use portpicker::Port;
+use tonic::{transport::Channel, Status};
+
+use crate::proto::{
+    compact_tx_streamer_client::CompactTxStreamerClient, BlockId, BlockRange, ChainSpec,
+    CompactBlock, Empty, LightdInfo, RawTransaction, SendResponse, TreeState, TxFilter,
+};
+
+/// Client connected to the `CompactTxStreamer` service of an indexer.
+#[derive(Clone, Debug)]
+pub struct IndexerClient {
+    inner: CompactTxStreamerClient<Channel>,
+}
+
+impl IndexerClient {
+    /// Connects to the indexer listening on `port`.
+    pub async fn connect(port: Port) -> Result<IndexerClient, tonic::transport::Error> {
+        let inner = CompactTxStreamerClient::connect(format!("http://127.0.0.1:{port}")).await?;
+        Ok(IndexerClient { inner })
+    }
+
+    /// Returns the underlying gRPC client for service methods without a wrapper.
+    pub fn inner(&mut self) -> &mut CompactTxStreamerClient<Channel> {
+        &mut self.inner
+    }
+
+    /// Returns the height and hash of the chain tip.
+    pub async fn get_latest_block(&mut self) -> Result<BlockId, Status> {
+        Ok(self
+            .inner
+            .get_latest_block(ChainSpec {})
+            .await?
+            .into_inner())
+    }
+
+    /// Returns the compact blocks from `start` to `end` inclusive.
+    ///
+    /// Blocks are returned in descending order if `start` is above `end`.
+    pub async fn get_block_range(
+        &mut self,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<CompactBlock>, Status> {
+        let mut stream = self
+            .inner
+            .get_block_range(BlockRange {
+                start: Some(BlockId {
+                    height: start,
+                    hash: Vec::new(),
+                }),
+                end: Some(BlockId {
+                    height: end,
+                    hash: Vec::new(),
+                }),
+            })
+            .await?
+            .into_inner();
+        let mut blocks = Vec::new();
+        while let Some(block) = stream.message().await? {
+            blocks.push(block);
+        }
+        Ok(blocks)
+    }
+
+    /// Returns the full transaction with the given `txid`.
+    pub async fn get_transaction(&mut self, txid: &[u8]) -> Result<RawTransaction, Status> {
+        Ok(self
+            .inner
+            .get_transaction(TxFilter {
+                block: None,
+                index: 0,
+                hash: txid.to_vec(),
+            })
+            .await?
+            .into_inner())
+    }
+
+    /// Returns the note commitment tree state of the block at `height`.
+    pub async fn get_tree_state(&mut self, height: u64) -> Result<TreeState, Status> {
+        Ok(self
+            .inner
+            .get_tree_state(BlockId {
+                height,
+                hash: Vec::new(),
+            })
+            .await?
+            .into_inner())
+    }
+
+    /// Submits the serialized `transaction` to the network.
+    ///
+    /// A non-zero [`SendResponse::error_code`] indicates the transaction was rejected.
+    pub async fn send_transaction(&mut self, transaction: Vec<u8>) -> Result<SendResponse, Status> {
+        Ok(self
+            .inner
+            .send_transaction(RawTransaction {
+                data: transaction,
+                height: 0,
+            })
+            .await?
+            .into_inner())
+    }
+
+    /// Returns information about the indexer and the state of the chain.
+    pub async fn get_lightd_info(&mut self) -> Result<LightdInfo, Status> {
+        Ok(self.inner.get_lightd_info(Empty {}).await?.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mock::MockIndexer,
+        proto::{CompactBlock, TreeState},
+        Indexer,
+    };
+
+    #[tokio::test]
+    async fn mock_indexer_client() {
+        let mock_indexer = MockIndexer::default();
+        for height in 1..=3 {
+            mock_indexer.chain().push_block(CompactBlock {
+                height,
+                ..Default::default()
+            });
+        }
+        mock_indexer.chain().add_tree_state(TreeState {
+            height: 2,
+            sapling_tree: "00".to_string(),
+            ..Default::default()
+        });
+
+        let mut client = mock_indexer.client().await.unwrap();
+        assert_eq!(client.get_latest_block().await.unwrap().height, 3);
+        let heights: Vec<u64> = client
+            .get_block_range(1, 3)
+            .await
+            .unwrap()
+            .iter()
+            .map(|block| block.height)
+            .collect();
+        assert_eq!(heights, vec![1, 2, 3]);
+        assert_eq!(client.get_tree_state(2).await.unwrap().sapling_tree, "00");
+        assert_eq!(client.get_lightd_info().await.unwrap().block_height, 3);
+    }
+}
@@ -10,16 +10,18 @@ use crate::network::ActivationHeights;
 
 pub(crate) const ZCASHD_FILENAME: &str = "zcash.conf";
 pub(crate) const ZAINOD_FILENAME: &str = "zindexer.toml";
-#[allow(dead_code)]
 pub(crate) const LIGHTWALLETD_FILENAME: &str = "lightwalletd.yml";
 
 /// Writes the Zcashd config file to the specified config directory.
 /// Returns the path to the config file.
+///
+/// If `zmq_port` is specified, Zcashd publishes `hashblock` and `hashtx` notifications on that port.
 pub(crate) fn zcashd(
     config_dir: &Path,
     rpc_port: Port,
     activation_heights: &ActivationHeights,
     miner_address: Option<&str>,
+    zmq_port: Option<Port>,
 ) -> std::io::Result<PathBuf> {
     let config_file_path = config_dir.join(ZCASHD_FILENAME);
     let mut config_file = File::create(config_file_path.clone())?;
@@ -73,6 +75,19 @@ minetolocalwallet=0 # This is set to false so that we can mine to a wallet, othe
         )?;
     }
 
+    if let Some(zmq_port) = zmq_port {
+        config_file.write_all(
+            format!(
+                "\n\n\
+### ZMQ Notifications:
+# https://zcash.readthedocs.io/en/latest/rtd_pages/zmq.html
+zmqpubhashblock=tcp://127.0.0.1:{zmq_port}
+zmqpubhashtx=tcp://127.0.0.1:{zmq_port}"
+            )
+            .as_bytes(),
+        )?;
+    }
+
     Ok(config_file_path)
 }
 
@@ -128,27 +143,47 @@ idle_worker_pool_size = 4"
 
 /// Writes the Lightwalletd config file to the specified config directory.
 /// Returns the path to the config file.
-#[allow(dead_code)]
+///
+/// `zcashd_conf` is the path to the validator config file Lightwalletd reads RPC settings from.
+/// Use `None` for darkside mode, where Lightwalletd serves staged blocks instead of connecting to a validator.
 pub(crate) fn lightwalletd(
     config_dir: &Path,
-    grpc_bind_addr_port: Port
+    grpc_bind_addr_port: Port,
+    log_file: &Path,
+    zcashd_conf: Option<&Path>,
 ) -> std::io::Result<PathBuf> {
     let config_file_path = config_dir.join(LIGHTWALLETD_FILENAME);
     let mut config_file = File::create(config_file_path.clone())?;
 
+    let log_file = log_file.to_str().expect("should be valid UTF-8");
     config_file.write_all(format!("\
 grpc-bind-addr: 127.0.0.1:{grpc_bind_addr_port}
 cache-size: 10
-log-file: ../logs/lwd.log
-log-level: 10
-zcash-conf-path: ./zcash.conf"
+log-file: {log_file}
+log-level: 10"
     ).as_bytes())?;
 
+    if let Some(zcashd_conf) = zcashd_conf {
+        let zcashd_conf = zcashd_conf.to_str().expect("should be valid UTF-8");
+        config_file.write_all(format!("\nzcash-conf-path: {zcashd_conf}").as_bytes())?;
+    }
+
     Ok(config_file_path)
 }
 
+/// Returns the port Zcashd publishes `hashblock` notifications on, read from the contents of a Zcashd config file.
+pub(crate) fn zcashd_zmq_port(conf: &str) -> Option<Port> {
+    conf.lines().find_map(|line| {
+        let endpoint = line.trim().strip_prefix("zmqpubhashblock=")?;
+        let endpoint = endpoint.split('#').next()?.trim();
+        endpoint.rsplit_once(':')?.1.parse().ok()
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use crate::network::ActivationHeights;
 
     #[test]
@@ -163,7 +198,7 @@ mod tests {
             nu5: 6.into(),
         };
 
-        super::zcashd(config_dir.path(), 1234, &activation_heights, None).unwrap();
+        super::zcashd(config_dir.path(), 1234, &activation_heights, None, None).unwrap();
 
         assert_eq!(std::fs::read_to_string(config_dir.path().join(super::ZCASHD_FILENAME)).unwrap(),
                         format!("\
@@ -212,7 +247,7 @@ listen=0"
             nu5: 6.into(),
         };
 
-        super::zcashd(config_dir.path(), 1234, &activation_heights, Some("test_addr_1234")).unwrap();
+        super::zcashd(config_dir.path(), 1234, &activation_heights, Some("test_addr_1234"), None).unwrap();
 
         assert_eq!(std::fs::read_to_string(config_dir.path().join(super::ZCASHD_FILENAME)).unwrap(),
                         format!("\
@@ -253,11 +288,54 @@ minetolocalwallet=0 # This is set to false so that we can mine to a wallet, othe
         );
     }
 
+    #[test]
+    fn zcashd_zmq_port() {
+        assert_eq!(
+            super::zcashd_zmq_port("regtest=1\nzmqpubhashblock=tcp://127.0.0.1:5678\n"),
+            Some(5678)
+        );
+        assert_eq!(super::zcashd_zmq_port("regtest=1\n"), None);
+    }
+
+    #[test]
+    fn zcashd_zmq() {
+        let config_dir = tempfile::tempdir().unwrap();
+
+        super::zcashd(
+            config_dir.path(),
+            1234,
+            &ActivationHeights::default(),
+            None,
+            Some(5678),
+        )
+        .unwrap();
+
+        assert!(
+            std::fs::read_to_string(config_dir.path().join(super::ZCASHD_FILENAME))
+                .unwrap()
+                .ends_with(
+                    "\
+listen=0
+
+### ZMQ Notifications:
+# https://zcash.readthedocs.io/en/latest/rtd_pages/zmq.html
+zmqpubhashblock=tcp://127.0.0.1:5678
+zmqpubhashtx=tcp://127.0.0.1:5678"
+                )
+        );
+    }
+
     #[test]
     fn lightwalletd() {
         let config_dir = tempfile::tempdir().unwrap();
 
-        super::lightwalletd(config_dir.path(), 1234).unwrap();
+        super::lightwalletd(
+            config_dir.path(),
+            1234,
+            Path::new("../logs/lwd.log"),
+            Some(Path::new("./zcash.conf")),
+        )
+        .unwrap();
 
         assert_eq!(std::fs::read_to_string(config_dir.path().join(super::LIGHTWALLETD_FILENAME)).unwrap(),
             format!(
@@ -270,4 +348,20 @@ zcash-conf-path: ./zcash.conf"
             )
         )
     }
+
+    #[test]
+    fn lightwalletd_darkside() {
+        let config_dir = tempfile::tempdir().unwrap();
+
+        super::lightwalletd(config_dir.path(), 1234, Path::new("/dev/stdout"), None).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(config_dir.path().join(super::LIGHTWALLETD_FILENAME)).unwrap(),
+            "\
+grpc-bind-addr: 127.0.0.1:1234
+cache-size: 10
+log-file: /dev/stdout
+log-level: 10"
+        )
+    }
 }
@@ -0,0 +1,203 @@
+//! Client for the lightwalletd darkside `DarksideStreamer` gRPC service
+//!
+//! In darkside mode, lightwalletd serves blocks and transactions staged by the test instead of those of a validator.
+//! Blocks and transactions are staged with the `stage_*` methods and served once [`DarksideClient::apply_staged`]
+//! is called, which allows precise control over reorgs.
+
+use portpicker::Port;
+use tonic::{transport::Channel, Status};
+
+use crate::proto::{
+    darkside_streamer_client::DarksideStreamerClient, BlockId, DarksideBlock, DarksideBlocksUrl,
+    DarksideEmptyBlocks, DarksideHeight, DarksideMetaState, DarksideSubtreeRoots,
+    DarksideTransactionsUrl, Empty, GetAddressUtxosReply, RawTransaction, ShieldedProtocol,
+    SubtreeRoot, TreeState,
+};
+
+/// Client connected to the `DarksideStreamer` service of a lightwalletd launched in darkside mode.
+#[derive(Clone, Debug)]
+pub struct DarksideClient {
+    inner: DarksideStreamerClient<Channel>,
+}
+
+impl DarksideClient {
+    /// Connects to the darkside service listening on `port`.
+    pub async fn connect(port: Port) -> Result<DarksideClient, tonic::transport::Error> {
+        let inner = DarksideStreamerClient::connect(format!("http://127.0.0.1:{port}")).await?;
+        Ok(DarksideClient { inner })
+    }
+
+    /// Resets all darkside and lightwalletd state and sets the chain parameters reported by `GetLightdInfo`.
+    ///
+    /// The block range starts at `sapling_activation`. `branch_id` is the consensus branch id (hex) and
+    /// `chain_name` is the network name, e.g. "regtest".
+    pub async fn reset(
+        &mut self,
+        sapling_activation: i32,
+        branch_id: &str,
+        chain_name: &str,
+    ) -> Result<(), Status> {
+        self.inner
+            .reset(DarksideMetaState {
+                sapling_activation,
+                branch_id: branch_id.to_string(),
+                chain_name: chain_name.to_string(),
+                start_sapling_commitment_tree_size: 0,
+                start_orchard_commitment_tree_size: 0,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Stages serialized `blocks` (hex), in order, until [`DarksideClient::apply_staged`] is called.
+    pub async fn stage_blocks(&mut self, blocks: Vec<String>) -> Result<(), Status> {
+        self.inner
+            .stage_blocks_stream(tokio_stream::iter(
+                blocks.into_iter().map(|block| DarksideBlock { block }),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Stages blocks fetched from `url`, one serialized block (hex) per line.
+    pub async fn stage_blocks_url(&mut self, url: &str) -> Result<(), Status> {
+        self.inner
+            .stage_blocks(DarksideBlocksUrl {
+                url: url.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Stages `count` empty blocks at consecutive heights starting at `height`.
+    ///
+    /// The `nonce` contributes to the block hashes, so blocks replacing existing ones in a reorg can be created by
+    /// staging the same heights with a different nonce.
+    pub async fn stage_empty_blocks(
+        &mut self,
+        height: i32,
+        nonce: i32,
+        count: i32,
+    ) -> Result<(), Status> {
+        self.inner
+            .stage_blocks_create(DarksideEmptyBlocks {
+                height,
+                nonce,
+                count,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Stages serialized `transactions` to be mined into the block at `height` when staged blocks are applied.
+    pub async fn stage_transactions(
+        &mut self,
+        height: u64,
+        transactions: Vec<Vec<u8>>,
+    ) -> Result<(), Status> {
+        self.inner
+            .stage_transactions_stream(tokio_stream::iter(
+                transactions
+                    .into_iter()
+                    .map(move |data| RawTransaction { data, height }),
+            ))
+            .await?;
+        Ok(())
+    }
+
+    /// Stages transactions fetched from `url`, one serialized transaction (hex) per line, to be mined into the block
+    /// at `height`.
+    pub async fn stage_transactions_url(&mut self, height: i32, url: &str) -> Result<(), Status> {
+        self.inner
+            .stage_transactions(DarksideTransactionsUrl {
+                height,
+                url: url.to_string(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Applies the staged blocks and transactions to the active chain and sets the chain tip to `height`.
+    pub async fn apply_staged(&mut self, height: i32) -> Result<(), Status> {
+        self.inner.apply_staged(DarksideHeight { height }).await?;
+        Ok(())
+    }
+
+    /// Returns and clears the transactions submitted to lightwalletd with `SendTransaction`.
+    pub async fn get_incoming_transactions(&mut self) -> Result<Vec<RawTransaction>, Status> {
+        let mut stream = self
+            .inner
+            .get_incoming_transactions(Empty {})
+            .await?
+            .into_inner();
+        let mut transactions = Vec::new();
+        while let Some(transaction) = stream.message().await? {
+            transactions.push(transaction);
+        }
+        Ok(transactions)
+    }
+
+    /// Clears the transactions submitted to lightwalletd with `SendTransaction`.
+    pub async fn clear_incoming_transactions(&mut self) -> Result<(), Status> {
+        self.inner.clear_incoming_transactions(Empty {}).await?;
+        Ok(())
+    }
+
+    /// Adds a transparent output to be returned by `GetAddressUtxos`.
+    pub async fn add_address_utxo(&mut self, utxo: GetAddressUtxosReply) -> Result<(), Status> {
+        self.inner.add_address_utxo(utxo).await?;
+        Ok(())
+    }
+
+    /// Clears the transparent outputs returned by `GetAddressUtxos`.
+    pub async fn clear_address_utxos(&mut self) -> Result<(), Status> {
+        self.inner.clear_address_utxo(Empty {}).await?;
+        Ok(())
+    }
+
+    /// Sets the tree state returned by `GetTreeState` for the block at `tree_state.height`.
+    pub async fn set_tree_state(&mut self, tree_state: TreeState) -> Result<(), Status> {
+        self.inner.add_tree_state(tree_state).await?;
+        Ok(())
+    }
+
+    /// Removes the tree state of the block at `height`.
+    pub async fn remove_tree_state(&mut self, height: u64) -> Result<(), Status> {
+        self.inner
+            .remove_tree_state(BlockId {
+                height,
+                hash: Vec::new(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Removes all tree states.
+    pub async fn clear_tree_states(&mut self) -> Result<(), Status> {
+        self.inner.clear_all_tree_states(Empty {}).await?;
+        Ok(())
+    }
+
+    /// Replaces the subtree roots returned by `GetSubtreeRoots` for `protocol`, starting at `start_index`.
+    pub async fn set_subtree_roots(
+        &mut self,
+        protocol: ShieldedProtocol,
+        start_index: u32,
+        subtree_roots: Vec<SubtreeRoot>,
+    ) -> Result<(), Status> {
+        self.inner
+            .set_subtree_roots(DarksideSubtreeRoots {
+                shielded_protocol: protocol.into(),
+                start_index,
+                subtree_roots,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Requests lightwalletd to shut down.
+    pub async fn stop(&mut self) -> Result<(), Status> {
+        self.inner.stop(Empty {}).await?;
+        Ok(())
+    }
+}
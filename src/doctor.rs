@@ -0,0 +1,197 @@
+//! Module for checking that the local environment can launch processes
+//!
+//! [`check`] reports which binaries are found and their versions, whether the Zcash proving parameters are present,
+//! whether local ports can be bound and whether the temporary directory has enough free space.
+//!
+//! Example usage:
+//! ```ignore (incomplete)
+//! let report = doctor::check(None);
+//! println!("{report}");
+//! assert!(report.is_ok());
+//! ```
+
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+};
+
+use portpicker::Port;
+
+use crate::{
+    binaries::{self, Binary, ResolvedBinary},
+    error::LaunchError,
+    params,
+};
+
+/// Binaries reported by [`check`]
+pub const BINARIES: [Binary; 5] = [
+    Binary::Zcashd,
+    Binary::ZcashCli,
+    Binary::Zebrad,
+    Binary::Zainod,
+    Binary::Lightwalletd,
+];
+
+/// Number of ports bound by [`check`], enough for a validator, an indexer and their auxiliary ports
+pub const PORTS_CHECKED: usize = 4;
+
+/// Free space required in the temporary directory, which holds the data, config and logs directories
+pub const MIN_TEMP_SPACE: u64 = 1 << 30;
+
+/// Environment report returned by [`check`]
+#[derive(Debug)]
+pub struct DoctorReport {
+    /// Resolution result of each of the [`BINARIES`]
+    pub binaries: Vec<(Binary, Result<ResolvedBinary, LaunchError>)>,
+    /// Result of the params verification, see [`crate::params::verify`]
+    pub params: Result<(), LaunchError>,
+    /// Ports which were bound, or the reason no free port could be bound
+    pub ports: Result<Vec<Port>, String>,
+    /// Temporary directory
+    pub temp_dir: PathBuf,
+    /// Free space in the temporary directory in bytes, if it could be determined
+    pub temp_space: Option<u64>,
+}
+
+impl DoctorReport {
+    /// Returns `true` if no check failed.
+    ///
+    /// Binaries which are not found are not failures, as not every binary is needed. Binaries with unsupported
+    /// versions are.
+    pub fn is_ok(&self) -> bool {
+        self.binaries
+            .iter()
+            .all(|(_, result)| matches!(result, Ok(_) | Err(LaunchError::BinaryNotFound { .. })))
+            && self.params.is_ok()
+            && self.ports.is_ok()
+            && !matches!(self.temp_space, Some(space) if space < MIN_TEMP_SPACE)
+    }
+}
+
+impl std::fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Binaries:")?;
+        for (binary, result) in &self.binaries {
+            match result {
+                Ok(resolved) => {
+                    let version = resolved.version.map_or_else(
+                        || "unknown version".to_string(),
+                        |version| version.to_string(),
+                    );
+                    writeln!(
+                        f,
+                        "  ok       {binary} {version} at {} ({:?})",
+                        resolved.path.display(),
+                        resolved.source
+                    )?;
+                }
+                Err(LaunchError::BinaryNotFound { .. }) => writeln!(
+                    f,
+                    "  missing  {binary}: not found, set {} or add it to $PATH",
+                    binary.env_var()
+                )?,
+                Err(e) => writeln!(f, "  FAIL     {binary}: {e}")?,
+            }
+        }
+
+        match &self.params {
+            Ok(()) => writeln!(f, "Params:    ok")?,
+            Err(e) => writeln!(f, "Params:    FAIL {e}")?,
+        }
+
+        match &self.ports {
+            Ok(ports) => writeln!(f, "Ports:     ok, bound {ports:?}")?,
+            Err(e) => writeln!(f, "Ports:     FAIL {e}")?,
+        }
+
+        let temp_dir = self.temp_dir.display();
+        match self.temp_space {
+            Some(space) if space >= MIN_TEMP_SPACE => {
+                write!(f, "Temp dir:  ok, {} MiB free in {temp_dir}", space >> 20)
+            }
+            Some(space) => write!(
+                f,
+                "Temp dir:  FAIL {} MiB free in {temp_dir}, {} MiB required",
+                space >> 20,
+                MIN_TEMP_SPACE >> 20
+            ),
+            None => write!(f, "Temp dir:  free space in {temp_dir} unknown"),
+        }
+    }
+}
+
+/// Checks the local environment and returns a [`DoctorReport`].
+///
+/// Use `params_dir` to check the params directory passed to Zcashd, see
+/// [`crate::LaunchOptions::params_dir`].
+pub fn check(params_dir: Option<&Path>) -> DoctorReport {
+    let temp_dir = std::env::temp_dir();
+    DoctorReport {
+        binaries: BINARIES
+            .iter()
+            .map(|binary| (*binary, binaries::resolve(*binary, None, false)))
+            .collect(),
+        params: params::verify(params_dir),
+        ports: bind_ports(),
+        temp_space: available_space(&temp_dir),
+        temp_dir,
+    }
+}
+
+/// Picks and binds [`PORTS_CHECKED`] ports on localhost, holding them until all are bound.
+fn bind_ports() -> Result<Vec<Port>, String> {
+    let mut listeners = Vec::with_capacity(PORTS_CHECKED);
+    for _ in 0..PORTS_CHECKED {
+        let port = portpicker::pick_unused_port().ok_or("no free port found")?;
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("failed to bind port {port}: {e}"))?;
+        listeners.push((port, listener));
+    }
+    Ok(listeners.into_iter().map(|(port, _)| port).collect())
+}
+
+/// Returns the space available to unprivileged users on the filesystem containing `path`.
+#[cfg(unix)]
+fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs is plain data, for which all zero bytes is a valid value
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stat` is a valid statvfs struct
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::LaunchError;
+
+    #[test]
+    fn check() {
+        let params_dir = tempfile::tempdir().unwrap();
+        let report = super::check(Some(params_dir.path()));
+
+        assert_eq!(report.binaries.len(), super::BINARIES.len());
+        assert!(matches!(
+            report.params,
+            Err(LaunchError::MissingParams { ref missing, .. }) if missing.len() == 3
+        ));
+        assert_eq!(report.ports.as_ref().unwrap().len(), super::PORTS_CHECKED);
+        #[cfg(unix)]
+        assert!(report.temp_space.unwrap() > 0);
+        assert!(!report.is_ok());
+
+        let output = report.to_string();
+        assert!(output.contains("zcashd"));
+        assert!(output.contains("Params:    FAIL"));
+    }
+}
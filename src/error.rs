@@ -17,4 +17,223 @@ pub enum LaunchError {
         /// Stderr log
         stderr: String,
     },
+    /// Process logged a line matching the failure matcher during launch
+    #[error(
+        "{process_name} logged a failure during launch: {line}\nStdout: {stdout}\nStderr: {stderr}"
+    )]
+    FailureLogged {
+        /// Process name
+        process_name: String,
+        /// Log line matching the failure matcher
+        line: String,
+        /// Stdout log
+        stdout: String,
+        /// Stderr log
+        stderr: String,
+    },
+    /// Binary was not found at the specified path or in $PATH
+    #[error("{binary} binary not found at \"{path}\". Specify its path, set {env_var} or add it to $PATH")]
+    BinaryNotFound {
+        /// Binary name
+        binary: String,
+        /// Path or name which was looked up
+        path: String,
+        /// Environment variable specifying the path to the binary
+        env_var: String,
+    },
+    /// Binary version is older than the versions supported by this crate
+    #[error("{binary} {version} at {path} is not supported. Supported versions: {supported}")]
+    UnsupportedVersion {
+        /// Binary name
+        binary: String,
+        /// Path to the binary
+        path: String,
+        /// Version reported by the binary
+        version: String,
+        /// Supported version range
+        supported: String,
+    },
+    /// Zcash proving parameters are missing or fail the checksum, see [`crate::params`]
+    #[error(
+        "Zcash params in {params_dir} are missing or corrupted. Run zcash-fetch-params or set the params directory.\nMissing: {}\nInvalid: {}",
+        format_paths(.missing),
+        format_paths(.invalid)
+    )]
+    MissingParams {
+        /// Params directory which was checked
+        params_dir: String,
+        /// Paths to the params files which are absent
+        missing: Vec<std::path::PathBuf>,
+        /// Paths to the params files which have the wrong size, could not be read or fail the checksum
+        invalid: Vec<std::path::PathBuf>,
+    },
+    /// Externally managed process could not be reached when attaching to it
+    #[error("failed to attach to {process_name} on port {port}: {message}")]
+    AttachFailed {
+        /// Process name
+        process_name: String,
+        /// Port which was checked
+        port: u16,
+        /// Connection or RPC error
+        message: String,
+    },
+    /// Config files could not be written or the process could not be spawned
+    #[error("{process_name} could not be set up for launch: {message}")]
+    Setup {
+        /// Process name
+        process_name: String,
+        /// Error message
+        message: String,
+    },
+}
+
+/// Errors of processes launched concurrently with [`crate::parallel::launch_all`]
+#[derive(thiserror::Error, Debug, Clone)]
+#[error("{} of {total} processes failed to launch:{}", .failures.len(), format_failures(.failures))]
+pub struct ParallelLaunchError {
+    /// Number of processes launched
+    pub total: usize,
+    /// Index and error of each process which failed to launch
+    pub failures: Vec<(usize, LaunchError)>,
+}
+
+fn format_paths(paths: &[std::path::PathBuf]) -> String {
+    if paths.is_empty() {
+        return "none".to_string();
+    }
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_failures(failures: &[(usize, LaunchError)]) -> String {
+    failures
+        .iter()
+        .map(|(index, error)| format!("\n[{index}] {error}"))
+        .collect()
+}
+
+/// Errors associated with connection manifests, see [`crate::manifest`]
+#[derive(thiserror::Error, Debug)]
+pub enum ManifestError {
+    /// Manifest file could not be read
+    #[error("failed to read manifest {path}: {source}")]
+    Read {
+        /// Path to the manifest
+        path: String,
+        /// Read error
+        source: std::io::Error,
+    },
+    /// Manifest file could not be written
+    #[error("failed to write manifest {path}: {source}")]
+    Write {
+        /// Path to the manifest
+        path: String,
+        /// Write error
+        source: std::io::Error,
+    },
+    /// Manifest file is not a valid manifest
+    #[error("invalid manifest {path}: {source}")]
+    Parse {
+        /// Path to the manifest
+        path: String,
+        /// Parse error
+        source: serde_json::Error,
+    },
+    /// Process listed in the manifest has exited
+    #[error("{process_name} with pid {pid} listed in the manifest is not running")]
+    NotRunning {
+        /// Process name
+        process_name: String,
+        /// Process id
+        pid: u32,
+    },
+}
+
+/// Errors associated with connecting to the Lightwalletd darkside service
+#[derive(thiserror::Error, Debug)]
+pub enum DarksideError {
+    /// Lightwalletd was not launched in darkside mode, so it does not serve the darkside service
+    #[error(
+        "lightwalletd was not launched in darkside mode, launch it with Lightwalletd::launch_darkside"
+    )]
+    NotDarkside,
+    /// Connection to the darkside service failed
+    #[error("failed to connect to the darkside service: {0}")]
+    Connect(#[from] tonic::transport::Error),
+}
+
+/// Errors associated with JSON-RPC calls
+#[derive(thiserror::Error, Debug)]
+pub enum RpcError {
+    /// Request could not be sent or the response could not be read
+    #[error("RPC transport failed: {0}")]
+    Transport(#[from] std::io::Error),
+    /// Response was not a JSON-RPC response
+    #[error("invalid RPC response. HTTP status: {status}\nBody: {body}")]
+    InvalidResponse {
+        /// HTTP status
+        status: u16,
+        /// Response body
+        body: String,
+    },
+    /// Server returned a JSON-RPC error
+    #[error("RPC error {code}: {message}")]
+    Rpc {
+        /// JSON-RPC error code
+        code: i64,
+        /// JSON-RPC error message
+        message: String,
+    },
+}
+
+/// Errors associated with waiting on process state
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum WaitError {
+    /// Indexer did not sync to the validator's best block before the timeout
+    #[error(
+        "timed out waiting for indexer to sync. Indexer height: {indexer_height:?}, validator height: {validator_height:?}"
+    )]
+    SyncTimeout {
+        /// Last height reported by the indexer, if any
+        indexer_height: Option<u64>,
+        /// Last height reported by the validator, if any
+        validator_height: Option<u64>,
+    },
+    /// Validator did not reach the target height before the timeout
+    #[error(
+        "timed out waiting for validator to reach height {target}. Validator height: {height:?}"
+    )]
+    HeightTimeout {
+        /// Target height
+        target: u32,
+        /// Last height reported by the validator, if any
+        height: Option<u32>,
+    },
+    /// Transaction did not enter the validator's mempool before the timeout
+    #[error("timed out waiting for transaction {txid} to enter the mempool")]
+    MempoolTimeout {
+        /// Transaction id
+        txid: String,
+    },
+    /// Transaction did not reach the target number of confirmations before the timeout
+    #[error(
+        "timed out waiting for transaction {txid} to reach {target} confirmations. Confirmations: {confirmations:?}"
+    )]
+    ConfirmationsTimeout {
+        /// Transaction id
+        txid: String,
+        /// Target number of confirmations
+        target: u32,
+        /// Last number of confirmations reported by the validator, if the transaction was mined
+        confirmations: Option<u32>,
+    },
+    /// No log line matched the pattern before the timeout
+    #[error("timed out waiting for a log line matching \"{pattern}\"")]
+    LogLineTimeout {
+        /// Log line pattern
+        pattern: String,
+    },
 }
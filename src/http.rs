@@ -0,0 +1,328 @@
+//! Minimal blocking HTTP/1.1 transport used by the crate's JSON-RPC servers and clients
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use portpicker::Port;
+
+use crate::network;
+
+/// HTTP request received by an [`HttpServer`].
+#[derive(Debug, Clone)]
+pub(crate) struct HttpRequest {
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Returns the value of the first header matching `name` (case-insensitive).
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// HTTP response returned by an [`HttpServer`] handler or read by [`post`].
+#[derive(Debug, Clone)]
+pub(crate) struct HttpResponse {
+    pub(crate) status: u16,
+    pub(crate) body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Creates a JSON response with the given `status`.
+    pub(crate) fn json(status: u16, body: &serde_json::Value) -> Self {
+        HttpResponse {
+            status,
+            body: body.to_string().into_bytes(),
+        }
+    }
+}
+
+type Handler = dyn Fn(HttpRequest) -> HttpResponse + Send + Sync;
+
+/// HTTP server listening on localhost, handling each connection on its own thread.
+///
+/// The server stops accepting connections when dropped.
+pub(crate) struct HttpServer {
+    port: Port,
+    shutdown: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// Binds the server to `fixed_port`, or a random free port if `None`, and starts serving requests with `handler`.
+    pub(crate) fn bind<F>(fixed_port: Option<Port>, handler: F) -> std::io::Result<HttpServer>
+    where
+        F: Fn(HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    {
+        let port = network::pick_unused_port(fixed_port);
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let accept_shutdown = shutdown.clone();
+        let accept_thread = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let handler = handler.clone();
+                        std::thread::spawn(move || serve_connection(stream, handler.as_ref()));
+                    }
+                    Err(e) => tracing::warn!("failed to accept connection: {e}"),
+                }
+            }
+        });
+
+        Ok(HttpServer {
+            port,
+            shutdown,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Returns the port the server is listening on.
+    pub(crate) fn port(&self) -> Port {
+        self.port
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wake the accept loop so it observes the shutdown flag
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve_connection(stream: TcpStream, handler: &Handler) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            tracing::warn!("failed to clone connection: {e}");
+            return;
+        }
+    };
+    let mut reader = BufReader::new(stream);
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::debug!("closing connection after read failure: {e}");
+                break;
+            }
+        };
+        let close = request
+            .header("connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        let response = handler(request);
+        if let Err(e) = write_response(&mut writer, &response) {
+            tracing::debug!("closing connection after write failure: {e}");
+            break;
+        }
+        if close {
+            break;
+        }
+    }
+    let _ = writer.shutdown(Shutdown::Both);
+}
+
+/// Sends a `POST /` request with a JSON `body` to the server listening on `port` and reads the full response.
+pub(crate) fn post(
+    port: Port,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> std::io::Result<HttpResponse> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+    stream.write_all(&post_request(port, headers, body))?;
+    stream.flush()?;
+
+    read_response(&mut BufReader::new(stream))
+}
+
+/// Async version of [`post`].
+#[cfg(feature = "async")]
+pub(crate) async fn post_async(
+    port: Port,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> std::io::Result<HttpResponse> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port)).await?;
+    stream.write_all(&post_request(port, headers, body)).await?;
+    stream.flush().await?;
+
+    // the server closes the connection after the response
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    read_response(&mut response.as_slice())
+}
+
+fn post_request(port: Port, headers: &[(String, String)], body: &[u8]) -> Vec<u8> {
+    let mut request = format!(
+        "POST / HTTP/1.1\r\n\
+        Host: 127.0.0.1:{port}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n",
+        body.len()
+    );
+    for (name, value) in headers.iter().filter(|(name, _)| !is_hop_header(name)) {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    let mut request = request.into_bytes();
+    request.extend_from_slice(body);
+    request
+}
+
+/// Returns `true` for headers that are set per connection and must not be forwarded.
+fn is_hop_header(name: &str) -> bool {
+    ["host", "content-length", "content-type", "connection"]
+        .iter()
+        .any(|hop| name.eq_ignore_ascii_case(hop))
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Start line and headers of an HTTP message.
+type MessageHead = (String, Vec<(String, String)>);
+
+/// Reads the start line and headers of an HTTP message. Returns `None` if the connection was closed.
+fn read_head<R: BufRead>(reader: &mut R) -> std::io::Result<Option<MessageHead>> {
+    let mut start_line = String::new();
+    if reader.read_line(&mut start_line)? == 0 {
+        return Ok(None);
+    }
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("connection closed while reading headers"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid_data("malformed header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    Ok(Some((start_line.trim_end().to_string(), headers)))
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> std::io::Result<Option<HttpRequest>> {
+    let Some((_request_line, headers)) = read_head(reader)? else {
+        return Ok(None);
+    };
+    let content_length = content_length(&headers)?.unwrap_or(0);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Some(HttpRequest { headers, body }))
+}
+
+fn read_response<R: BufRead>(reader: &mut R) -> std::io::Result<HttpResponse> {
+    let (status_line, headers) =
+        read_head(reader)?.ok_or_else(|| invalid_data("connection closed before response"))?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid_data("malformed status line"))?;
+
+    let body = if let Some(content_length) = content_length(&headers)? {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        body
+    } else if find_header(&headers, "transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        read_chunked(reader)?
+    } else {
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        body
+    };
+
+    Ok(HttpResponse { status, body })
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line)?;
+        let size_hex = size_line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| invalid_data("malformed chunk size"))?;
+        if size == 0 {
+            // consume trailer section
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 2 {
+                line.clear();
+            }
+            return Ok(body);
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf)?;
+    }
+}
+
+fn content_length(headers: &[(String, String)]) -> std::io::Result<Option<usize>> {
+    find_header(headers, "content-length")
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid_data("malformed content length"))
+        })
+        .transpose()
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &HttpResponse) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        _ => "Unknown",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {reason}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        \r\n",
+        response.status,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
@@ -1,17 +1,59 @@
 #![warn(missing_docs)]
 //! Zcash Localnet
 
-use std::{fs::File, io::Read, path::PathBuf, process::Child};
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    process::Child,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Duration,
+};
 
-use error::LaunchError;
+use artifacts::ArtifactOptions;
+use binaries::Binary;
+use capture::{CapturedLine, LogCapture, LogEvent};
+use client::IndexerClient;
+use darkside::DarksideClient;
+use error::{DarksideError, LaunchError, WaitError};
 use getset::Getters;
+use logs::{LogStream, ProcessLogs};
+use manifest::{ManifestEntry, ProcessManifest, RpcEndpoint};
+use matcher::{MatchState, Matcher};
 use network::ActivationHeights;
+use notify::ChainEvent;
+use orphans::PidFile;
 use portpicker::Port;
+use recording::RpcRecorder;
+use rpc::{RpcClient, RpcCredentials};
 use tempfile::TempDir;
 
+pub mod artifacts;
+pub mod attached;
+pub mod binaries;
+pub(crate) mod capture;
+pub mod client;
 pub(crate) mod config;
+pub mod darkside;
+pub mod doctor;
 pub mod error;
+pub(crate) mod http;
+pub mod local_process;
+pub mod logs;
+pub mod manifest;
+pub mod matcher;
+pub mod matrix;
+pub mod mock;
 pub mod network;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod notify;
+pub mod orphans;
+pub mod parallel;
+pub mod params;
+pub mod proto;
+pub mod recording;
+pub mod rpc;
+pub(crate) mod wait;
 
 const STDOUT_LOG: &str = "stdout.log";
 const STDERR_LOG: &str = "stderr.log";
@@ -20,6 +62,7 @@ const STDERR_LOG: &str = "stderr.log";
 enum Process {
     Zcashd,
     Zainod,
+    Lightwalletd,
 }
 
 impl std::fmt::Display for Process {
@@ -27,75 +70,544 @@ impl std::fmt::Display for Process {
         let process = match self {
             Self::Zcashd => "zcashd",
             Self::Zainod => "zainod",
+            Self::Lightwalletd => "lightwalletd",
         };
         write!(f, "{}", process)
     }
 }
 
+impl Process {
+    /// Returns the default matcher for the log line indicating the process is ready.
+    fn ready_matcher(self) -> Matcher {
+        let line = match self {
+            Self::Zcashd => "init message: Done loading",
+            Self::Zainod => "Server Ready.",
+            Self::Lightwalletd => "Starting gRPC server",
+        };
+        Matcher::contains(line).on_stream(LogStream::Stdout)
+    }
+
+    /// Returns the default matcher for log lines indicating the process failed to launch.
+    fn failure_matcher(self) -> Matcher {
+        match self {
+            Self::Zcashd | Self::Zainod => Matcher::contains("Error:"),
+            Self::Lightwalletd => Matcher::contains("fatal"),
+        }
+    }
+}
+
+/// Functionality for indexers serving the light wallet `CompactTxStreamer` gRPC service.
+pub trait Indexer {
+    /// Returns the port serving the `CompactTxStreamer` gRPC service.
+    fn listen_port(&self) -> Port;
+
+    /// Connects a [`crate::client::IndexerClient`] to the indexer.
+    fn client(
+        &self,
+    ) -> impl Future<Output = Result<IndexerClient, tonic::transport::Error>> + Send {
+        IndexerClient::connect(self.listen_port())
+    }
+
+    /// Waits until the indexer's latest block matches the best block of `validator`.
+    ///
+    /// Polls the indexer and the validator's `getblockcount` / `getbestblockhash` until both report the same chain
+    /// tip, e.g. after generating blocks. Returns [`crate::error::WaitError::SyncTimeout`] with the last heights
+    /// reported by each process if they do not match within `timeout`.
+    fn wait_until_synced<V: Validator>(
+        &self,
+        validator: &V,
+        timeout: Duration,
+    ) -> Result<(), WaitError> {
+        wait::until_synced(self.listen_port(), &validator.rpc_client(), timeout)
+    }
+}
+
+/// Functionality for validators serving the JSON-RPC interface.
+pub trait Validator {
+    /// Returns the JSON-RPC port.
+    fn rpc_port(&self) -> Port;
+
+    /// Returns a [`crate::rpc::RpcClient`] for the validator's JSON-RPC interface.
+    fn rpc_client(&self) -> RpcClient {
+        RpcClient::new(self.rpc_port(), RpcCredentials::default())
+    }
+
+    /// Waits until the validator's chain reaches `height`, e.g. after generating blocks.
+    ///
+    /// Polls `getblockcount` with backoff. Returns [`crate::error::WaitError::HeightTimeout`] if the height is not
+    /// reached within `timeout`.
+    fn wait_for_height(&self, height: u32, timeout: Duration) -> Result<(), WaitError> {
+        wait::for_height(&self.rpc_client(), height, timeout)
+    }
+
+    /// Waits until the transaction with the given `txid` (hex, RPC byte order) is in the validator's mempool.
+    ///
+    /// Polls `getrawmempool` with backoff. Returns [`crate::error::WaitError::MempoolTimeout`] if the transaction
+    /// does not enter the mempool within `timeout`.
+    fn wait_for_mempool_tx(&self, txid: &str, timeout: Duration) -> Result<(), WaitError> {
+        wait::for_mempool_tx(&self.rpc_client(), txid, timeout)
+    }
+
+    /// Waits until the transaction with the given `txid` (hex, RPC byte order) has at least `confirmations`
+    /// confirmations.
+    ///
+    /// Polls `getrawtransaction` with backoff. Returns [`crate::error::WaitError::ConfirmationsTimeout`] if the
+    /// transaction does not reach `confirmations` within `timeout`.
+    fn wait_for_confirmations(
+        &self,
+        txid: &str,
+        confirmations: u32,
+        timeout: Duration,
+    ) -> Result<(), WaitError> {
+        wait::for_confirmations(&self.rpc_client(), txid, confirmations, timeout)
+    }
+}
+
+/// Options for launching processes, common to all process types.
+///
+/// Example usage for forwarding Zcashd logs to the test's `tracing` subscriber:
+/// ```ignore (incomplete)
+/// let options = LaunchOptions {
+///     trace_logs: true,
+///     ..Default::default()
+/// };
+/// let zcashd = Zcashd::launch_with_options(None, None, None, &activation_heights, None, &options)?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LaunchOptions {
+    /// Forward each stdout and stderr line of the process as a `tracing` event.
+    ///
+    /// Events are emitted inside a span tagged with the process name, pid and port, so the logs of all processes
+    /// show interleaved through the test's subscriber. Logs are captured to the logs directory regardless.
+    pub trace_logs: bool,
+    /// Preserve the logs, config file and optionally data of the process when its handle is dropped.
+    ///
+    /// Defaults to [`crate::artifacts::ArtifactOptions::from_env`] if `None`.
+    pub artifacts: Option<ArtifactOptions>,
+    /// Matcher for the log lines indicating the process is ready, overriding the default for the process type.
+    pub ready_matcher: Option<Matcher>,
+    /// Matcher for the log lines indicating the process failed to launch, overriding the default for the process
+    /// type.
+    pub failure_matcher: Option<Matcher>,
+    /// Matchers for known benign log lines which are never treated as failures, e.g. errors logged during normal
+    /// startup. Each line is matched individually, so a [`crate::matcher::Matcher::All`] ignores only lines matched by
+    /// all of its matchers.
+    pub ignored_failures: Vec<Matcher>,
+    /// Keep the process running if the test process exits without dropping its handle.
+    ///
+    /// On Linux, processes are killed when the test process exits so they are not orphaned if it crashes. Set this
+    /// to leave the process running, e.g. for a network used after the test process exits. See [`crate::orphans`].
+    pub allow_orphans: bool,
+    /// Launch binaries without checking that their version is supported. See [`crate::binaries::resolve`].
+    pub skip_version_check: bool,
+    /// Directory containing the Zcash proving parameters, passed to Zcashd as `-paramsdir`.
+    ///
+    /// Defaults to the Zcashd default if `None`, see [`crate::params::default_params_dir`]. The parameters are
+    /// checked before launching Zcashd, see [`crate::params::check`]. This is the only supported way to set
+    /// `-paramsdir`, as the check does not read the Zcashd config.
+    pub params_dir: Option<PathBuf>,
+    /// Connection manifest to add the process to, see [`crate::manifest`].
+    ///
+    /// The process is removed from the manifest when its handle is dropped.
+    pub manifest: Option<PathBuf>,
+    /// Publish block and transaction notifications over ZMQ on a port picked at random, see
+    /// [`crate::Zcashd::subscribe`]. Zcashd only.
+    pub zmq_notifications: bool,
+}
+
+impl LaunchOptions {
+    /// Returns the artifact options, with the test name taken from the launching thread if not set.
+    fn artifacts(&self) -> Option<ArtifactOptions> {
+        let mut artifacts = self.artifacts.clone().or_else(ArtifactOptions::from_env)?;
+        if artifacts.test_name.is_none() {
+            artifacts.test_name = std::thread::current().name().map(str::to_string);
+        }
+        Some(artifacts)
+    }
+}
+
+/// Output captured from a process during launch, reported if the launch fails.
+#[derive(Default)]
+struct LaunchOutput {
+    stdout: String,
+    stderr: String,
+}
+
+impl LaunchOutput {
+    fn push(&mut self, line: &CapturedLine) {
+        let log = match line.stream {
+            LogStream::Stdout => &mut self.stdout,
+            LogStream::Stderr => &mut self.stderr,
+        };
+        log.push_str(&line.line);
+        log.push('\n');
+    }
+}
+
+/// Tracks the output of a launching process until it is ready or fails.
+struct LaunchMonitor<'a> {
+    process_name: String,
+    ready: MatchState<'a>,
+    failure: MatchState<'a>,
+    ignored_failures: &'a [Matcher],
+    output: LaunchOutput,
+    open_streams: usize,
+}
+
+impl<'a> LaunchMonitor<'a> {
+    /// `default_ready` and `default_failure` are used unless overridden by `options`.
+    fn new(
+        process_name: String,
+        default_ready: &'a Matcher,
+        default_failure: &'a Matcher,
+        options: &'a LaunchOptions,
+    ) -> Self {
+        LaunchMonitor {
+            process_name,
+            ready: MatchState::new(options.ready_matcher.as_ref().unwrap_or(default_ready)),
+            failure: MatchState::new(options.failure_matcher.as_ref().unwrap_or(default_failure)),
+            ignored_failures: &options.ignored_failures,
+            output: LaunchOutput::default(),
+            open_streams: 2,
+        }
+    }
+
+    /// Feeds the next log event.
+    ///
+    /// Returns `Some(Ok(()))` once a line matched the readiness matcher, or [`LaunchError::FailureLogged`] if a line
+    /// not ignored by the options matched the failure matcher.
+    fn feed(&mut self, event: LogEvent) -> Option<Result<(), LaunchError>> {
+        let LogEvent::Line(line) = event else {
+            self.open_streams -= 1;
+            return None;
+        };
+        self.output.push(&line);
+        let ignored = self
+            .ignored_failures
+            .iter()
+            .any(|matcher| matcher.is_match(line.stream, &line.line));
+        if !ignored && self.failure.feed(line.stream, &line.line) {
+            let output = std::mem::take(&mut self.output);
+            Some(Err(LaunchError::FailureLogged {
+                process_name: self.process_name.clone(),
+                line: line.line.to_string(),
+                stdout: output.stdout,
+                stderr: output.stderr,
+            }))
+        } else if self.ready.feed(line.stream, &line.line) {
+            Some(Ok(()))
+        } else {
+            None
+        }
+    }
+
+    /// Collects the output of an event received after the process exited.
+    fn drain(&mut self, event: LogEvent) {
+        match event {
+            LogEvent::Line(line) => self.output.push(&line),
+            LogEvent::Closed => self.open_streams -= 1,
+        }
+    }
+
+    /// Returns whether the output streams may still send lines.
+    fn streams_open(&self) -> bool {
+        self.open_streams > 0
+    }
+
+    fn process_failed(self, exit_status: std::process::ExitStatus) -> LaunchError {
+        LaunchError::ProcessFailed {
+            process_name: self.process_name,
+            exit_status,
+            stdout: self.output.stdout,
+            stderr: self.output.stderr,
+        }
+    }
+}
+
+/// Interval for checking whether a launching process exited while no log lines are received
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Creates the logs directory and log capture of a launching process.
+fn capture_logs(
+    process: &impl std::fmt::Display,
+    pid: Option<u32>,
+    port: Option<Port>,
+    options: &LaunchOptions,
+) -> (TempDir, LogCapture) {
+    let logs_dir = tempfile::tempdir().unwrap();
+    let span = options
+        .trace_logs
+        .then(|| tracing::info_span!("process", name = %process, pid, port));
+    let log_capture = LogCapture::new(logs_dir.path(), span).unwrap();
+    (logs_dir, log_capture)
+}
+
+/// Waits until the process logs a line matched by the readiness matcher.
+///
+/// `default_ready` and `default_failure` are used unless overridden by `options`. If the failure matcher matches a
+/// line not ignored by `options`, the process is killed and [`LaunchError::FailureLogged`] is returned.
 fn wait_for_launch(
-    process: Process,
+    process: impl std::fmt::Display,
     handle: &mut Child,
-    success_indicator: &str,
-    error_indicator: &str,
+    port: Option<Port>,
+    default_ready: Matcher,
+    default_failure: Matcher,
+    options: &LaunchOptions,
 ) -> Result<TempDir, LaunchError> {
-    let logs_dir = tempfile::tempdir().unwrap();
+    let (logs_dir, log_capture) = capture_logs(&process, Some(handle.id()), port, options);
+    let events = log_capture.subscribe();
+    log_capture.capture(LogStream::Stdout, handle.stdout.take().unwrap());
+    log_capture.capture(LogStream::Stderr, handle.stderr.take().unwrap());
 
-    let stdout_log_path = logs_dir.path().join(STDOUT_LOG);
-    let mut stdout_log = File::create(&stdout_log_path).unwrap();
-    let mut stdout = handle.stdout.take().unwrap();
-    std::thread::spawn(move || {
-        std::io::copy(&mut stdout, &mut stdout_log)
-            .expect("should be able to read/write stdout log");
-    });
-    let mut stdout_log = File::open(stdout_log_path).expect("should be able to open log");
-    let mut stdout = String::new();
-
-    let stderr_log_path = logs_dir.path().join(STDERR_LOG);
-    let mut stderr_log = File::create(&stderr_log_path).unwrap();
-    let mut stderr = handle.stderr.take().unwrap();
-    std::thread::spawn(move || {
-        std::io::copy(&mut stderr, &mut stderr_log)
-            .expect("should be able to read/write stderr log");
-    });
-    let mut stderr_log = File::open(stderr_log_path).expect("should be able to open log");
-    let mut stderr = String::new();
-
-    // wait for stdout log entry that indicates daemon is ready
-    let interval = std::time::Duration::from_millis(100);
+    let mut monitor = LaunchMonitor::new(
+        process.to_string(),
+        &default_ready,
+        &default_failure,
+        options,
+    );
+
+    // wait for log entry that indicates daemon is ready
     loop {
+        match events.recv_timeout(EXIT_POLL_INTERVAL) {
+            Ok(event) => {
+                let is_line = matches!(event, LogEvent::Line(_));
+                match monitor.feed(event) {
+                    // launch successful
+                    Some(Ok(())) => break,
+                    Some(Err(e)) => {
+                        if let Err(kill_error) = handle.kill().and_then(|_| handle.wait()) {
+                            tracing::warn!(
+                                "{process} could not be killed after failing: {kill_error}"
+                            );
+                        }
+                        return Err(e);
+                    }
+                    None if is_line => continue,
+                    None => (),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => unreachable!("capture holds a sender"),
+        }
+
         match handle.try_wait() {
             Ok(Some(exit_status)) => {
-                stdout_log.read_to_string(&mut stdout).unwrap();
-                stderr_log.read_to_string(&mut stderr).unwrap();
-
-                return Err(LaunchError::ProcessFailed {
-                    process_name: process.to_string(),
-                    exit_status,
-                    stdout,
-                    stderr,
-                });
+                // collect the output written before exiting, unless the streams are held open by another process
+                while monitor.streams_open() {
+                    match events.recv_timeout(EXIT_POLL_INTERVAL) {
+                        Ok(event) => monitor.drain(event),
+                        Err(_) => break,
+                    }
+                }
+
+                return Err(monitor.process_failed(exit_status));
             }
             Ok(None) => (),
             Err(e) => {
                 panic!("Unexpected Error: {e}")
             }
         };
+    }
+
+    Ok(logs_dir)
+}
+
+/// Async version of [`wait_for_launch`].
+#[cfg(feature = "async")]
+async fn wait_for_launch_async(
+    process: impl std::fmt::Display,
+    handle: &mut tokio::process::Child,
+    port: Option<Port>,
+    default_ready: Matcher,
+    default_failure: Matcher,
+    options: &LaunchOptions,
+) -> Result<TempDir, LaunchError> {
+    let (logs_dir, log_capture) = capture_logs(&process, handle.id(), port, options);
+    let mut events = log_capture.subscribe_async();
+    log_capture.capture_async(LogStream::Stdout, handle.stdout.take().unwrap());
+    log_capture.capture_async(LogStream::Stderr, handle.stderr.take().unwrap());
+
+    let mut monitor = LaunchMonitor::new(
+        process.to_string(),
+        &default_ready,
+        &default_failure,
+        options,
+    );
 
-        stdout_log.read_to_string(&mut stdout).unwrap();
-        stderr_log.read_to_string(&mut stderr).unwrap();
-        if stdout.contains(error_indicator) || stderr.contains(error_indicator) {
-            panic!("{} launch failed without reporting an error code!\nexiting with panic. you may have to shut the daemon down manually.", process);
-        } else if stdout.contains(success_indicator) {
-            // launch successful
-            break;
+    loop {
+        match tokio::time::timeout(EXIT_POLL_INTERVAL, events.recv()).await {
+            Ok(Some(event)) => {
+                let is_line = matches!(event, LogEvent::Line(_));
+                match monitor.feed(event) {
+                    Some(Ok(())) => break,
+                    Some(Err(e)) => {
+                        if let Err(kill_error) = handle.kill().await {
+                            tracing::warn!(
+                                "{process} could not be killed after failing: {kill_error}"
+                            );
+                        }
+                        return Err(e);
+                    }
+                    None if is_line => continue,
+                    None => (),
+                }
+            }
+            Err(_elapsed) => (),
+            Ok(None) => unreachable!("capture holds a sender"),
         }
 
-        std::thread::sleep(interval);
+        match handle.try_wait() {
+            Ok(Some(exit_status)) => {
+                while monitor.streams_open() {
+                    match tokio::time::timeout(EXIT_POLL_INTERVAL, events.recv()).await {
+                        Ok(Some(event)) => monitor.drain(event),
+                        _ => break,
+                    }
+                }
+
+                return Err(monitor.process_failed(exit_status));
+            }
+            Ok(None) => (),
+            Err(e) => {
+                panic!("Unexpected Error: {e}")
+            }
+        };
     }
 
     Ok(logs_dir)
 }
 
+/// Launch of a process prepared before spawning it, shared by the blocking and async APIs.
+struct PreparedLaunch {
+    process: Process,
+    port: Port,
+    config_dir: TempDir,
+    /// Path to the data directory, recorded in the pid file
+    data_dir: Option<PathBuf>,
+    /// Manifest entry, completed with the pid and logs directory once launched
+    manifest: ProcessManifest,
+}
+
+/// Process launched from a [`PreparedLaunch`], which is ready.
+struct LaunchedProcess<C> {
+    handle: C,
+    logs_dir: TempDir,
+    artifacts: Option<ArtifactOptions>,
+    pid_file: PidFile,
+    manifest_entry: Option<ManifestEntry>,
+}
+
+impl PreparedLaunch {
+    /// Spawns `command` and waits until the process is ready.
+    fn launch(
+        &self,
+        command: std::process::Command,
+        options: &LaunchOptions,
+    ) -> Result<LaunchedProcess<Child>, LaunchError> {
+        let (mut handle, pid_file) =
+            orphans::spawn(command, &self.process.to_string(), options.allow_orphans)
+                .map_err(|e| self.spawn_error(e))?;
+        let logs_dir = wait_for_launch(
+            self.process,
+            &mut handle,
+            Some(self.port),
+            self.process.ready_matcher(),
+            self.process.failure_matcher(),
+            options,
+        )?;
+        let pid = handle.id();
+        Ok(self.launched(handle, pid, pid_file, logs_dir, options))
+    }
+
+    /// Async version of [`PreparedLaunch::launch`].
+    #[cfg(feature = "async")]
+    async fn launch_async(
+        &self,
+        command: std::process::Command,
+        options: &LaunchOptions,
+    ) -> Result<LaunchedProcess<tokio::process::Child>, LaunchError> {
+        let (mut handle, pid_file) =
+            orphans::spawn_async(command, &self.process.to_string(), options.allow_orphans)
+                .map_err(|e| self.spawn_error(e))?;
+        let pid = handle.id().expect("process should not be awaited yet");
+        let logs_dir = wait_for_launch_async(
+            self.process,
+            &mut handle,
+            Some(self.port),
+            self.process.ready_matcher(),
+            self.process.failure_matcher(),
+            options,
+        )
+        .await?;
+        Ok(self.launched(handle, pid, pid_file, logs_dir, options))
+    }
+
+    /// Returns the error for a process which failed to spawn.
+    fn spawn_error(&self, error: std::io::Error) -> LaunchError {
+        LaunchError::Setup {
+            process_name: self.process.to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    /// Records the directories of the launched process in its pid file and adds it to the manifest.
+    fn launched<C>(
+        &self,
+        handle: C,
+        pid: u32,
+        mut pid_file: PidFile,
+        logs_dir: TempDir,
+        options: &LaunchOptions,
+    ) -> LaunchedProcess<C> {
+        let mut dirs: Vec<&Path> = self.data_dir.iter().map(PathBuf::as_path).collect();
+        dirs.extend([logs_dir.path(), self.config_dir.path()]);
+        pid_file.set_dirs(&dirs);
+        let manifest_entry = ManifestEntry::register(
+            options.manifest.as_deref(),
+            ProcessManifest {
+                pid,
+                start_time: orphans::start_time(pid),
+                logs_dir: logs_dir.path().to_path_buf(),
+                ..self.manifest.clone()
+            },
+        );
+
+        LaunchedProcess {
+            handle,
+            logs_dir,
+            artifacts: options.artifacts(),
+            pid_file,
+            manifest_entry,
+        }
+    }
+}
+
+/// Returns the Zcash-cli command, or "zcash-cli" from $PATH if `zcash_cli_bin` is `None`, with the given config file
+/// and `args`.
+fn zcash_cli(
+    zcash_cli_bin: Option<&Path>,
+    config_path: &Path,
+    args: &[&str],
+) -> std::process::Command {
+    let mut command = match zcash_cli_bin {
+        Some(path) => std::process::Command::new(path),
+        None => std::process::Command::new("zcash-cli"),
+    };
+
+    command.arg(format!("-conf={}", config_path.to_str().unwrap()));
+    command.args(args);
+    command
+}
+
+/// Zcashd launch prepared by [`Zcashd::prepare`].
+struct PreparedZcashd {
+    command: std::process::Command,
+    launch: PreparedLaunch,
+    data_dir: TempDir,
+    zcash_cli_bin: Option<PathBuf>,
+    zmq_port: Option<Port>,
+}
+
 /// This struct is used to represent and manage the Zcashd process.
 #[derive(Getters)]
 #[getset(get = "pub")]
@@ -104,6 +616,10 @@ pub struct Zcashd {
     handle: Child,
     /// RPC Port
     port: Port,
+    /// RPC credentials
+    credentials: RpcCredentials,
+    /// ZMQ notification port, `None` unless launched with [`crate::LaunchOptions::zmq_notifications`]
+    zmq_port: Option<Port>,
     /// Data directory
     _data_dir: TempDir,
     /// Logs directory
@@ -112,13 +628,25 @@ pub struct Zcashd {
     config_dir: TempDir,
     /// Path to zcash cli binary
     zcash_cli_bin: Option<PathBuf>,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
 }
 
 impl Zcashd {
     /// Launches Zcashd process and returns [`crate::Zcashd`] with the handle and associated directories.
     ///
     /// Use `zcashd_bin` and `zcash_cli_bin` to specify the paths to the binaries.
-    /// If these binaries are in $PATH, `None` can be specified to run "zcashd" / "zcash-cli".
+    /// If `None` is specified, the binaries are located with the `ZCASHD_BIN` / `ZCASH_CLI_BIN` environment
+    /// variables or in $PATH. The Zcashd version is checked before launching, see [`crate::binaries::resolve`].
+    /// The Zcash proving parameters are also checked, returning [`crate::error::LaunchError::MissingParams`] if they
+    /// are absent, see [`crate::params::check`].
     ///
     /// Use `fixed_port` to specify a port for Zcashd. Otherwise, a port is picked at random.
     ///
@@ -132,17 +660,97 @@ impl Zcashd {
         activation_heights: &ActivationHeights,
         miner_address: Option<&str>,
     ) -> Result<Zcashd, LaunchError> {
+        Zcashd::launch_with_options(
+            zcashd_bin,
+            zcash_cli_bin,
+            rpc_port,
+            activation_heights,
+            miner_address,
+            &LaunchOptions::default(),
+        )
+    }
+
+    /// Launches Zcashd process with the given [`crate::LaunchOptions`].
+    ///
+    /// See [`crate::Zcashd::launch`].
+    pub fn launch_with_options(
+        zcashd_bin: Option<PathBuf>,
+        zcash_cli_bin: Option<PathBuf>,
+        rpc_port: Option<Port>,
+        activation_heights: &ActivationHeights,
+        miner_address: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<Zcashd, LaunchError> {
+        let prepared = Zcashd::prepare(
+            zcashd_bin,
+            zcash_cli_bin,
+            rpc_port,
+            activation_heights,
+            miner_address,
+            options,
+        )?;
+        let launched = prepared.launch.launch(prepared.command, options)?;
+
+        Ok(Zcashd {
+            handle: launched.handle,
+            port: prepared.launch.port,
+            credentials: RpcCredentials::default(),
+            zmq_port: prepared.zmq_port,
+            _data_dir: prepared.data_dir,
+            logs_dir: launched.logs_dir,
+            config_dir: prepared.launch.config_dir,
+            zcash_cli_bin: prepared.zcash_cli_bin,
+            artifacts: launched.artifacts,
+            _pid_file: launched.pid_file,
+            _manifest_entry: launched.manifest_entry,
+        })
+    }
+
+    /// Returns the path to zcash-cli if it is found, see [`crate::binaries::find`].
+    fn find_zcash_cli(zcash_cli_bin: Option<PathBuf>) -> Option<PathBuf> {
+        binaries::find(Binary::ZcashCli, zcash_cli_bin.as_deref())
+            .map(|(path, _)| path)
+            .ok()
+            .or(zcash_cli_bin)
+    }
+
+    /// Resolves the binaries, checks the params and writes the config file, returning the launch to spawn.
+    ///
+    /// Blocks while the Zcashd version and params are checked.
+    fn prepare(
+        zcashd_bin: Option<PathBuf>,
+        zcash_cli_bin: Option<PathBuf>,
+        rpc_port: Option<Port>,
+        activation_heights: &ActivationHeights,
+        miner_address: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<PreparedZcashd, LaunchError> {
+        let zcashd_bin = binaries::resolve(
+            Binary::Zcashd,
+            zcashd_bin.as_deref(),
+            options.skip_version_check,
+        )?
+        .path;
+        params::check(options.params_dir.as_deref())?;
+        let zcash_cli_bin = Zcashd::find_zcash_cli(zcash_cli_bin);
         let port = network::pick_unused_port(rpc_port);
+        let zmq_port = options
+            .zmq_notifications
+            .then(|| network::pick_unused_port(None));
+
         let config_dir = tempfile::tempdir().unwrap();
-        let config_file_path =
-            config::zcashd(config_dir.path(), port, activation_heights, miner_address).unwrap();
+        let config_file_path = config::zcashd(
+            config_dir.path(),
+            port,
+            activation_heights,
+            miner_address,
+            zmq_port,
+        )
+        .unwrap();
 
         let data_dir = tempfile::tempdir().unwrap();
 
-        let mut command = match zcashd_bin {
-            Some(path) => std::process::Command::new(path),
-            None => std::process::Command::new("zcashd"),
-        };
+        let mut command = std::process::Command::new(zcashd_bin);
         command
             .args([
                 "--printtoconsole",
@@ -160,23 +768,33 @@ impl Zcashd {
             ])
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
+        if let Some(params_dir) = &options.params_dir {
+            command.arg(format!(
+                "-paramsdir={}",
+                params_dir.to_str().expect("should be valid UTF-8")
+            ));
+        }
 
-        let mut handle = command.spawn().unwrap();
-
-        let logs_dir = wait_for_launch(
-            Process::Zcashd,
-            &mut handle,
-            "init message: Done loading",
-            "Error:",
-        )?;
-
-        Ok(Zcashd {
-            handle,
-            port,
-            _data_dir: data_dir,
-            logs_dir,
-            config_dir,
+        Ok(PreparedZcashd {
+            command,
+            launch: PreparedLaunch {
+                process: Process::Zcashd,
+                port,
+                manifest: ProcessManifest {
+                    name: Process::Zcashd.to_string(),
+                    ports: [Some(port), zmq_port].into_iter().flatten().collect(),
+                    rpc: Some(RpcEndpoint::local(port)),
+                    config_path: Some(config_file_path),
+                    data_dir: Some(data_dir.path().to_path_buf()),
+                    activation_heights: Some(activation_heights.into()),
+                    ..Default::default()
+                },
+                data_dir: Some(data_dir.path().to_path_buf()),
+                config_dir,
+            },
+            data_dir,
             zcash_cli_bin,
+            zmq_port,
         })
     }
 
@@ -192,13 +810,7 @@ impl Zcashd {
     /// self.zcash_cli_command(&["generate", "1"]);
     /// ```
     pub fn zcash_cli_command(&self, args: &[&str]) -> std::io::Result<std::process::Output> {
-        let mut command = match &self.zcash_cli_bin {
-            Some(path) => std::process::Command::new(path),
-            None => std::process::Command::new("zcash-cli"),
-        };
-
-        command.arg(format!("-conf={}", self.config_path().to_str().unwrap()));
-        command.args(args).output()
+        zcash_cli(self.zcash_cli_bin.as_deref(), &self.config_path(), args).output()
     }
 
     /// Stops the Zcashd process.
@@ -228,13 +840,49 @@ impl Zcashd {
         self.zcash_cli_command(&["generate", &num_blocks.to_string()])
     }
 
+    /// Launches a [`crate::recording::RpcRecorder`] proxy in front of the Zcashd RPC port.
+    ///
+    /// Every JSON-RPC request/response pair sent through the proxy is recorded to the file at `recording_path`,
+    /// which should be outside the Zcashd directories so the recording outlives Zcashd, see
+    /// [`crate::recording::RPC_RECORDING_LOG`]. Launch the indexer with the recorder's port as the validator port to
+    /// record its traffic.
+    ///
+    /// Use `listen_port` to specify a port for the proxy. Otherwise, a port is picked at random.
+    pub fn record_rpc(
+        &self,
+        listen_port: Option<Port>,
+        recording_path: PathBuf,
+    ) -> std::io::Result<RpcRecorder> {
+        RpcRecorder::launch(self.port, listen_port, recording_path)
+    }
+
+    /// Subscribes to the block and transaction notifications published by Zcashd over ZMQ.
+    ///
+    /// Returns a receiver of [`crate::notify::ChainEvent`]s for blocks connected and transactions added to the
+    /// mempool. Each call creates an independent subscription. Zcashd must have been launched with
+    /// [`crate::LaunchOptions::zmq_notifications`], otherwise an error is returned.
+    ///
+    /// Zcashd applies the subscription asynchronously, so events published shortly after this call returns may be
+    /// missed. Receive with a timeout and generate another block if no event arrives.
+    ///
+    /// Example usage for waiting on a generated block:
+    /// ```ignore (incomplete)
+    /// let events = zcashd.subscribe()?;
+    /// zcashd.generate_blocks(1)?;
+    /// let ChainEvent::Block { hash } = events.recv_timeout(Duration::from_secs(10))? else { .. };
+    /// ```
+    pub fn subscribe(&self) -> std::io::Result<Receiver<ChainEvent>> {
+        notify::subscribe(self.zmq_port)
+    }
+
     /// Prints the stdout log.
     pub fn print_stdout(&self) {
-        let stdout_log_path = self.logs_dir.path().join(STDOUT_LOG);
-        let mut stdout_log = File::open(stdout_log_path).expect("should be able to open log");
-        let mut stdout = String::new();
-        stdout_log.read_to_string(&mut stdout).unwrap();
-        println!("{}", stdout);
+        println!("{}", self.stdout());
+    }
+
+    /// Prints the stderr log.
+    pub fn print_stderr(&self) {
+        println!("{}", self.stderr());
     }
 }
 
@@ -246,9 +894,38 @@ impl Default for Zcashd {
     }
 }
 
+impl Validator for Zcashd {
+    fn rpc_port(&self) -> Port {
+        self.port
+    }
+
+    fn rpc_client(&self) -> RpcClient {
+        RpcClient::new(self.port, self.credentials.clone())
+    }
+}
+
+impl ProcessLogs for Zcashd {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", Process::Zcashd, self.handle.id())
+    }
+}
+
 impl Drop for Zcashd {
     fn drop(&mut self) {
         self.stop();
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &Process::Zcashd.to_string(),
+                self.handle.id(),
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                Some(self._data_dir.path()),
+            );
+        }
     }
 }
 
@@ -264,6 +941,15 @@ pub struct Zainod {
     logs_dir: TempDir,
     /// Config directory
     config_dir: TempDir,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
 }
 
 impl Zainod {
@@ -277,36 +963,80 @@ impl Zainod {
         listen_port: Option<Port>,
         validator_port: Port,
     ) -> Result<Zainod, LaunchError> {
+        Zainod::launch_with_options(
+            zainod_bin,
+            listen_port,
+            validator_port,
+            &LaunchOptions::default(),
+        )
+    }
+
+    /// Launches Zainod process with the given [`crate::LaunchOptions`].
+    ///
+    /// See [`crate::Zainod::launch`].
+    pub fn launch_with_options(
+        zainod_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        validator_port: Port,
+        options: &LaunchOptions,
+    ) -> Result<Zainod, LaunchError> {
+        let (command, prepared) =
+            Zainod::prepare(zainod_bin, listen_port, validator_port, options)?;
+        let launched = prepared.launch(command, options)?;
+
+        Ok(Zainod {
+            handle: launched.handle,
+            port: prepared.port,
+            logs_dir: launched.logs_dir,
+            config_dir: prepared.config_dir,
+            artifacts: launched.artifacts,
+            _pid_file: launched.pid_file,
+            _manifest_entry: launched.manifest_entry,
+        })
+    }
+
+    /// Resolves the binary and writes the config file, returning the command and launch to spawn.
+    ///
+    /// Blocks while the Zainod version is checked.
+    fn prepare(
+        zainod_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        validator_port: Port,
+        options: &LaunchOptions,
+    ) -> Result<(std::process::Command, PreparedLaunch), LaunchError> {
+        let zainod_bin = binaries::resolve(
+            Binary::Zainod,
+            zainod_bin.as_deref(),
+            options.skip_version_check,
+        )?
+        .path;
         let port = network::pick_unused_port(listen_port);
         let config_dir = tempfile::tempdir().unwrap();
         let config_file_path = config::zainod(config_dir.path(), port, validator_port).unwrap();
 
-        let mut command = match zainod_bin {
-            Some(path) => std::process::Command::new(path),
-            None => std::process::Command::new("zainod"),
-        };
+        let mut command = std::process::Command::new(zainod_bin);
         command
             .args([
                 "--config",
-                format!(
-                    "{}",
-                    config_file_path.to_str().expect("should be valid UTF-8")
-                )
-                .as_str(),
+                config_file_path.to_str().expect("should be valid UTF-8"),
             ])
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped());
 
-        let mut handle = command.spawn().unwrap();
-
-        let logs_dir = wait_for_launch(Process::Zainod, &mut handle, "Server Ready.", "Error:")?;
-
-        Ok(Zainod {
-            handle,
+        let prepared = PreparedLaunch {
+            process: Process::Zainod,
             port,
-            logs_dir,
+            manifest: ProcessManifest {
+                name: Process::Zainod.to_string(),
+                ports: vec![port],
+                grpc_uri: Some(format!("http://127.0.0.1:{port}")),
+                config_path: Some(config_file_path),
+                ..Default::default()
+            },
+            data_dir: None,
             config_dir,
-        })
+        };
+        Ok((command, prepared))
     }
 
     /// Returns path to config file.
@@ -321,11 +1051,12 @@ impl Zainod {
 
     /// Prints the stdout log.
     pub fn print_stdout(&self) {
-        let stdout_log_path = self.logs_dir.path().join(STDOUT_LOG);
-        let mut stdout_log = File::open(stdout_log_path).expect("should be able to open log");
-        let mut stdout = String::new();
-        stdout_log.read_to_string(&mut stdout).unwrap();
-        println!("{}", stdout);
+        println!("{}", self.stdout());
+    }
+
+    /// Prints the stderr log.
+    pub fn print_stderr(&self) {
+        println!("{}", self.stderr());
     }
 }
 
@@ -337,8 +1068,273 @@ impl Default for Zainod {
     }
 }
 
+impl Indexer for Zainod {
+    fn listen_port(&self) -> Port {
+        self.port
+    }
+}
+
+impl ProcessLogs for Zainod {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", Process::Zainod, self.handle.id())
+    }
+}
+
 impl Drop for Zainod {
     fn drop(&mut self) {
         self.stop();
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &Process::Zainod.to_string(),
+                self.handle.id(),
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                None,
+            );
+        }
+    }
+}
+
+/// This struct is used to represent and manage the Lightwalletd process.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct Lightwalletd {
+    /// Child process handle
+    handle: Child,
+    /// gRPC Port
+    port: Port,
+    /// Data directory
+    _data_dir: TempDir,
+    /// Logs directory
+    logs_dir: TempDir,
+    /// Config directory
+    config_dir: TempDir,
+    /// Whether Lightwalletd was launched in darkside mode
+    darkside: bool,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
+}
+
+impl Lightwalletd {
+    /// Launches Lightwalletd process and returns [`crate::Lightwalletd`] with the handle and associated directories.
+    ///
+    /// Use `fixed_port` to specify a port for Lightwalletd. Otherwise, a port is picked at random.
+    ///
+    /// Lightwalletd reads the validator RPC settings from `zcashd_conf`, e.g. [`crate::Zcashd::config_path`].
+    /// The validator process must be running before launching Lightwalletd.
+    pub fn launch(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        zcashd_conf: PathBuf,
+    ) -> Result<Lightwalletd, LaunchError> {
+        Self::launch_mode(
+            lightwalletd_bin,
+            listen_port,
+            Some(zcashd_conf),
+            &LaunchOptions::default(),
+        )
+    }
+
+    /// Launches Lightwalletd process with the given [`crate::LaunchOptions`].
+    ///
+    /// See [`crate::Lightwalletd::launch`].
+    pub fn launch_with_options(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        zcashd_conf: PathBuf,
+        options: &LaunchOptions,
+    ) -> Result<Lightwalletd, LaunchError> {
+        Self::launch_mode(lightwalletd_bin, listen_port, Some(zcashd_conf), options)
+    }
+
+    /// Launches Lightwalletd process in darkside mode and returns [`crate::Lightwalletd`] with the handle and
+    /// associated directories.
+    ///
+    /// In darkside mode, Lightwalletd does not connect to a validator. Instead, it serves blocks and transactions
+    /// staged with a [`crate::darkside::DarksideClient`] (see [`crate::Lightwalletd::darkside_client`]).
+    ///
+    /// Use `fixed_port` to specify a port for Lightwalletd. Otherwise, a port is picked at random.
+    pub fn launch_darkside(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+    ) -> Result<Lightwalletd, LaunchError> {
+        Self::launch_mode(
+            lightwalletd_bin,
+            listen_port,
+            None,
+            &LaunchOptions::default(),
+        )
+    }
+
+    /// Launches Lightwalletd process in darkside mode with the given [`crate::LaunchOptions`].
+    ///
+    /// See [`crate::Lightwalletd::launch_darkside`].
+    pub fn launch_darkside_with_options(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        options: &LaunchOptions,
+    ) -> Result<Lightwalletd, LaunchError> {
+        Self::launch_mode(lightwalletd_bin, listen_port, None, options)
+    }
+
+    fn launch_mode(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        zcashd_conf: Option<PathBuf>,
+        options: &LaunchOptions,
+    ) -> Result<Lightwalletd, LaunchError> {
+        let (command, prepared, data_dir) = Lightwalletd::prepare(
+            lightwalletd_bin,
+            listen_port,
+            zcashd_conf.as_deref(),
+            options,
+        )?;
+        let launched = prepared.launch(command, options)?;
+
+        Ok(Lightwalletd {
+            handle: launched.handle,
+            port: prepared.port,
+            _data_dir: data_dir,
+            logs_dir: launched.logs_dir,
+            config_dir: prepared.config_dir,
+            darkside: zcashd_conf.is_none(),
+            artifacts: launched.artifacts,
+            _pid_file: launched.pid_file,
+            _manifest_entry: launched.manifest_entry,
+        })
+    }
+
+    /// Resolves the binary and writes the config file, returning the command, launch to spawn and data directory.
+    /// Lightwalletd is launched in darkside mode if `zcashd_conf` is `None`.
+    ///
+    /// Blocks while the Lightwalletd version is checked.
+    fn prepare(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        zcashd_conf: Option<&Path>,
+        options: &LaunchOptions,
+    ) -> Result<(std::process::Command, PreparedLaunch, TempDir), LaunchError> {
+        let lightwalletd_bin = binaries::resolve(
+            Binary::Lightwalletd,
+            lightwalletd_bin.as_deref(),
+            options.skip_version_check,
+        )?
+        .path;
+        let port = network::pick_unused_port(listen_port);
+        let config_dir = tempfile::tempdir().unwrap();
+        // logs are written to stdout to be captured with the other processes' logs
+        let config_file_path = config::lightwalletd(
+            config_dir.path(),
+            port,
+            Path::new("/dev/stdout"),
+            zcashd_conf,
+        )
+        .unwrap();
+
+        let data_dir = tempfile::tempdir().unwrap();
+
+        let mut command = std::process::Command::new(lightwalletd_bin);
+        command
+            .args([
+                "--no-tls-very-insecure",
+                "--config",
+                config_file_path.to_str().expect("should be valid UTF-8"),
+                "--data-dir",
+                data_dir.path().to_str().expect("should be valid UTF-8"),
+            ])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        if zcashd_conf.is_none() {
+            command.arg("--darkside-very-insecure");
+        }
+
+        let prepared = PreparedLaunch {
+            process: Process::Lightwalletd,
+            port,
+            manifest: ProcessManifest {
+                name: Process::Lightwalletd.to_string(),
+                ports: vec![port],
+                grpc_uri: Some(format!("http://127.0.0.1:{port}")),
+                config_path: Some(config_file_path),
+                data_dir: Some(data_dir.path().to_path_buf()),
+                ..Default::default()
+            },
+            data_dir: Some(data_dir.path().to_path_buf()),
+            config_dir,
+        };
+        Ok((command, prepared, data_dir))
+    }
+
+    /// Returns path to config file.
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.path().join(config::LIGHTWALLETD_FILENAME)
+    }
+
+    /// Connects a [`crate::darkside::DarksideClient`] to the darkside service.
+    ///
+    /// Returns [`crate::error::DarksideError::NotDarkside`] if Lightwalletd was not launched with
+    /// [`crate::Lightwalletd::launch_darkside`], as the darkside service is only served in darkside mode.
+    pub async fn darkside_client(&self) -> Result<DarksideClient, DarksideError> {
+        if !self.darkside {
+            return Err(DarksideError::NotDarkside);
+        }
+        Ok(DarksideClient::connect(self.port).await?)
+    }
+
+    /// Stops the Lightwalletd process.
+    pub fn stop(&mut self) {
+        self.handle.kill().expect("lightwalletd couldn't be killed")
+    }
+
+    /// Prints the stdout log.
+    pub fn print_stdout(&self) {
+        println!("{}", self.stdout());
+    }
+
+    /// Prints the stderr log.
+    pub fn print_stderr(&self) {
+        println!("{}", self.stderr());
+    }
+}
+
+impl Indexer for Lightwalletd {
+    fn listen_port(&self) -> Port {
+        self.port
+    }
+}
+
+impl ProcessLogs for Lightwalletd {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", Process::Lightwalletd, self.handle.id())
+    }
+}
+
+impl Drop for Lightwalletd {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &Process::Lightwalletd.to_string(),
+                self.handle.id(),
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                Some(self._data_dir.path()),
+            );
+        }
     }
 }
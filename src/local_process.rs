@@ -0,0 +1,297 @@
+//! Module for launching user-defined processes, e.g. wallet daemons or custom scanners
+//!
+//! [`LocalProcess`] manages a process with the same temporary directories, port allocation, log capture and
+//! readiness detection as the built-in processes.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Child,
+};
+
+use getset::Getters;
+use portpicker::Port;
+use tempfile::TempDir;
+
+use crate::{
+    artifacts::ArtifactOptions,
+    error::LaunchError,
+    logs::ProcessLogs,
+    manifest::{ManifestEntry, ProcessManifest},
+    matcher::Matcher,
+    network,
+    orphans::{self, PidFile},
+    LaunchOptions,
+};
+
+/// Callback writing the config files of a [`LocalProcess`] before launch.
+///
+/// Returns arguments appended to [`LocalProcessSpec::args`], e.g. `--config <path>`.
+pub type ConfigWriter = Box<dyn FnOnce(&LocalProcessEnv) -> std::io::Result<Vec<String>>>;
+
+/// Ports and directories allocated for a [`LocalProcess`], passed to its [`ConfigWriter`].
+#[derive(Debug)]
+pub struct LocalProcessEnv<'a> {
+    /// Allocated ports, in the order of [`LocalProcessSpec::ports`]
+    pub ports: &'a [Port],
+    /// Config directory
+    pub config_dir: &'a Path,
+    /// Data directory
+    pub data_dir: &'a Path,
+}
+
+/// Specification of a user-defined process launched with [`LocalProcess::launch`].
+///
+/// Example usage for a scanner reading its port from a config file:
+/// ```ignore (incomplete)
+/// let spec = LocalProcessSpec {
+///     name: "scanner".to_string(),
+///     command: PathBuf::from("scanner"),
+///     args: vec!["--verbose".to_string()],
+///     write_config: Some(Box::new(|env| {
+///         let config_path = env.config_dir.join("scanner.toml");
+///         std::fs::write(&config_path, format!("port = {}", env.ports[0]))?;
+///         Ok(vec!["--config".to_string(), config_path.to_str().unwrap().to_string()])
+///     })),
+///     ports: vec![None],
+///     ready_matcher: Matcher::contains("scanner started"),
+///     failure_matcher: Some(Matcher::contains("ERROR")),
+/// };
+/// let scanner = LocalProcess::launch(spec, &LaunchOptions::default())?;
+/// ```
+pub struct LocalProcessSpec {
+    /// Process name, used in logs, errors and artifacts
+    pub name: String,
+    /// Path to the binary, or a binary name to look up in $PATH
+    pub command: PathBuf,
+    /// Command arguments
+    pub args: Vec<String>,
+    /// Writes config files before launch
+    pub write_config: Option<ConfigWriter>,
+    /// Ports to allocate. Use `Some` to specify a port. Otherwise, a port is picked at random.
+    pub ports: Vec<Option<Port>>,
+    /// Matcher for the log line indicating the process is ready. Overridden by [`LaunchOptions::ready_matcher`].
+    pub ready_matcher: Matcher,
+    /// Matcher for log lines indicating the process failed to launch. Overridden by
+    /// [`LaunchOptions::failure_matcher`].
+    pub failure_matcher: Option<Matcher>,
+}
+
+/// This struct is used to represent and manage a user-defined process.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct LocalProcess {
+    /// Process name
+    name: String,
+    /// Child process handle
+    handle: Child,
+    /// Allocated ports, in the order of [`LocalProcessSpec::ports`]
+    ports: Vec<Port>,
+    /// Data directory
+    data_dir: TempDir,
+    /// Logs directory
+    logs_dir: TempDir,
+    /// Config directory
+    config_dir: TempDir,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
+}
+
+impl LocalProcess {
+    /// Launches the process described by `spec` and returns [`crate::local_process::LocalProcess`] with the handle
+    /// and associated directories.
+    ///
+    /// Ports are allocated and the config files written before the process is spawned. Returns once the process
+    /// logs a line matched by the readiness matcher.
+    pub fn launch(
+        spec: LocalProcessSpec,
+        options: &LaunchOptions,
+    ) -> Result<LocalProcess, LaunchError> {
+        let ports: Vec<Port> = spec
+            .ports
+            .iter()
+            .map(|port| network::pick_unused_port(*port))
+            .collect();
+        let config_dir = tempfile::tempdir().unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+
+        let mut args = spec.args;
+        if let Some(write_config) = spec.write_config {
+            let config_args = write_config(&LocalProcessEnv {
+                ports: &ports,
+                config_dir: config_dir.path(),
+                data_dir: data_dir.path(),
+            })
+            .map_err(|e| LaunchError::Setup {
+                process_name: spec.name.clone(),
+                message: e.to_string(),
+            })?;
+            args.extend(config_args);
+        }
+
+        let mut command = std::process::Command::new(&spec.command);
+        command
+            .args(args)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let (mut handle, mut pid_file) = orphans::spawn(command, &spec.name, options.allow_orphans)
+            .map_err(|e| LaunchError::Setup {
+                process_name: spec.name.clone(),
+                message: e.to_string(),
+            })?;
+
+        let logs_dir = crate::wait_for_launch(
+            &spec.name,
+            &mut handle,
+            ports.first().copied(),
+            spec.ready_matcher,
+            // an empty `Any` never matches
+            spec.failure_matcher.unwrap_or(Matcher::any(Vec::new())),
+            options,
+        )?;
+
+        pid_file.set_dirs(&[data_dir.path(), logs_dir.path(), config_dir.path()]);
+        let manifest_entry = ManifestEntry::register(
+            options.manifest.as_deref(),
+            ProcessManifest {
+                name: spec.name.clone(),
+                pid: handle.id(),
+                start_time: orphans::start_time(handle.id()),
+                ports: ports.clone(),
+                data_dir: Some(data_dir.path().to_path_buf()),
+                logs_dir: logs_dir.path().to_path_buf(),
+                ..Default::default()
+            },
+        );
+
+        Ok(LocalProcess {
+            name: spec.name,
+            handle,
+            ports,
+            data_dir,
+            logs_dir,
+            config_dir,
+            artifacts: options.artifacts(),
+            _pid_file: pid_file,
+            _manifest_entry: manifest_entry,
+        })
+    }
+
+    /// Stops the process.
+    pub fn stop(&mut self) {
+        if let Err(e) = self.handle.kill().and_then(|_| self.handle.wait()) {
+            tracing::error!("{} couldn't be killed: {e}", self.name);
+        }
+    }
+
+    /// Prints the stdout log.
+    pub fn print_stdout(&self) {
+        println!("{}", self.stdout());
+    }
+
+    /// Prints the stderr log.
+    pub fn print_stderr(&self) {
+        println!("{}", self.stderr());
+    }
+}
+
+impl ProcessLogs for LocalProcess {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", self.name, self.handle.id())
+    }
+}
+
+impl Drop for LocalProcess {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &self.name,
+                self.handle.id(),
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                Some(self.data_dir.path()),
+            );
+        }
+    }
+}
+
+// the tests run shell commands
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use crate::{
+        error::LaunchError,
+        logs::{LogLine, LogStream, ProcessLogs, Regex},
+        matcher::Matcher,
+        LaunchOptions,
+    };
+
+    use super::{LocalProcess, LocalProcessSpec};
+
+    #[test]
+    fn local_process() {
+        let port = portpicker::pick_unused_port().unwrap();
+        let spec = LocalProcessSpec {
+            name: "sidecar".to_string(),
+            command: PathBuf::from("sh"),
+            args: vec!["-c".to_string()],
+            write_config: Some(Box::new(|env| {
+                let config_path = env.config_dir.join("sidecar.conf");
+                std::fs::write(&config_path, format!("port={}", env.ports[0]))?;
+                Ok(vec![format!(
+                    "cat {}; echo; echo ready; sleep 60",
+                    config_path.display()
+                )])
+            })),
+            ports: vec![Some(port), None],
+            ready_matcher: Matcher::contains("ready"),
+            failure_matcher: None,
+        };
+        let sidecar = LocalProcess::launch(spec, &LaunchOptions::default()).unwrap();
+        assert_eq!(sidecar.ports()[0], port);
+        assert_eq!(sidecar.ports().len(), 2);
+        // the stdout log is written by a separate thread, which may lag behind the ready matcher
+        assert_eq!(
+            sidecar
+                .wait_for_log_line(
+                    &Regex::new(&format!("port={port}")).unwrap(),
+                    Duration::from_secs(5)
+                )
+                .unwrap(),
+            LogLine {
+                stream: LogStream::Stdout,
+                line: format!("port={port}"),
+            }
+        );
+        assert!(sidecar.process_label().starts_with("sidecar["));
+
+        let spec = LocalProcessSpec {
+            name: "sidecar".to_string(),
+            command: PathBuf::from("sh"),
+            args: vec![
+                "-c".to_string(),
+                "echo ERROR: bad config; sleep 60".to_string(),
+            ],
+            write_config: None,
+            ports: Vec::new(),
+            ready_matcher: Matcher::contains("ready"),
+            failure_matcher: Some(Matcher::contains("ERROR")),
+        };
+        assert!(matches!(
+            LocalProcess::launch(spec, &LaunchOptions::default()),
+            Err(LaunchError::FailureLogged { .. })
+        ));
+    }
+}
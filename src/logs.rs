@@ -0,0 +1,470 @@
+//! Module for reading the logs captured from launched processes
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub use regex::Regex;
+
+use crate::{error::WaitError, wait::Backoff, STDERR_LOG, STDOUT_LOG};
+
+/// File name of the log of both streams with the time each line was captured, in the logs directory of each process
+pub(crate) const TIMESTAMPED_LOG: &str = "timestamped.log";
+
+/// File name of the merged timeline written by [`write_timeline`]
+pub const TIMELINE_LOG: &str = "timeline.log";
+
+/// Output stream of a process
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogStream {
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+}
+
+impl LogStream {
+    fn file_name(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => STDOUT_LOG,
+            LogStream::Stderr => STDERR_LOG,
+        }
+    }
+}
+
+impl std::fmt::Display for LogStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stream = match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        };
+        write!(f, "{}", stream)
+    }
+}
+
+/// Line of a process log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogLine {
+    /// Stream the line was written to
+    pub stream: LogStream,
+    /// Line without the trailing newline
+    pub line: String,
+}
+
+/// Line of a process log with the time it was captured
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimestampedLine {
+    /// Time the line was read from the process output
+    pub time: SystemTime,
+    /// Log line
+    pub line: LogLine,
+}
+
+/// Position in the stdout and stderr logs of a process.
+///
+/// Created with [`ProcessLogs::log_cursor`] and advanced by [`ProcessLogs::lines_since`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogCursor {
+    stdout: u64,
+    stderr: u64,
+}
+
+impl LogCursor {
+    fn offset(&mut self, stream: LogStream) -> &mut u64 {
+        match stream {
+            LogStream::Stdout => &mut self.stdout,
+            LogStream::Stderr => &mut self.stderr,
+        }
+    }
+}
+
+/// Functionality for processes with captured stdout and stderr logs.
+pub trait ProcessLogs {
+    /// Returns the directory the stdout and stderr logs are written to.
+    fn logs_path(&self) -> &Path;
+
+    /// Returns the label identifying the process in a merged [`timeline`], e.g. "zcashd[1234]".
+    fn process_label(&self) -> String;
+
+    /// Returns the path to the log of `stream`.
+    fn log_path(&self, stream: LogStream) -> PathBuf {
+        self.logs_path().join(stream.file_name())
+    }
+
+    /// Returns the log of `stream` captured so far.
+    fn log(&self, stream: LogStream) -> String {
+        let mut log = String::new();
+        File::open(self.log_path(stream))
+            .expect("should be able to open log")
+            .read_to_string(&mut log)
+            .expect("should be able to read log");
+        log
+    }
+
+    /// Returns the stdout log captured so far.
+    fn stdout(&self) -> String {
+        self.log(LogStream::Stdout)
+    }
+
+    /// Returns the stderr log captured so far.
+    fn stderr(&self) -> String {
+        self.log(LogStream::Stderr)
+    }
+
+    /// Returns the last `num_lines` lines of the log of `stream`.
+    fn tail(&self, stream: LogStream, num_lines: usize) -> Vec<String> {
+        let log = self.log(stream);
+        let lines: Vec<&str> = log.lines().collect();
+        lines[lines.len().saturating_sub(num_lines)..]
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    /// Returns the lines of the stdout and stderr logs matching `pattern`, stdout lines first.
+    fn grep(&self, pattern: &Regex) -> Vec<LogLine> {
+        [LogStream::Stdout, LogStream::Stderr]
+            .into_iter()
+            .flat_map(|stream| {
+                self.log(stream)
+                    .lines()
+                    .filter(|line| pattern.is_match(line))
+                    .map(|line| LogLine {
+                        stream,
+                        line: line.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Returns a cursor positioned at the end of the logs captured so far.
+    fn log_cursor(&self) -> LogCursor {
+        let mut cursor = LogCursor::default();
+        for stream in [LogStream::Stdout, LogStream::Stderr] {
+            *cursor.offset(stream) = std::fs::metadata(self.log_path(stream))
+                .expect("should be able to read log metadata")
+                .len();
+        }
+        cursor
+    }
+
+    /// Returns the complete lines logged since `cursor`, stdout lines first, and advances `cursor` past them.
+    ///
+    /// Example usage for asserting on the logs of an action:
+    /// ```ignore (incomplete)
+    /// let mut cursor = zainod.log_cursor();
+    /// zcashd.generate_blocks(1)?;
+    /// let lines = zainod.lines_since(&mut cursor);
+    /// ```
+    fn lines_since(&self, cursor: &mut LogCursor) -> Vec<LogLine> {
+        let mut lines = Vec::new();
+        for stream in [LogStream::Stdout, LogStream::Stderr] {
+            let offset = cursor.offset(stream);
+            let mut log = File::open(self.log_path(stream)).expect("should be able to open log");
+            log.seek(SeekFrom::Start(*offset))
+                .expect("should be able to seek log");
+            let mut new_bytes = Vec::new();
+            log.read_to_end(&mut new_bytes)
+                .expect("should be able to read log");
+            // a partially written line is left for the next call
+            let complete_len = new_bytes
+                .iter()
+                .rposition(|byte| *byte == b'\n')
+                .map_or(0, |newline| newline + 1);
+            *offset += complete_len as u64;
+            lines.extend(
+                String::from_utf8_lossy(&new_bytes[..complete_len])
+                    .lines()
+                    .map(|line| LogLine {
+                        stream,
+                        line: line.to_string(),
+                    }),
+            );
+        }
+        lines
+    }
+
+    /// Returns the lines of the stdout and stderr logs in the order they were captured, with the time each line was
+    /// captured.
+    fn timestamped_lines(&self) -> Vec<TimestampedLine> {
+        let mut log = String::new();
+        File::open(self.logs_path().join(TIMESTAMPED_LOG))
+            .expect("should be able to open log")
+            .read_to_string(&mut log)
+            .expect("should be able to read log");
+        log.lines()
+            .filter_map(|entry| {
+                let (micros, entry) = entry.split_once(' ')?;
+                let (stream, line) = entry.split_once(' ')?;
+                let stream = match stream {
+                    "stdout" => LogStream::Stdout,
+                    "stderr" => LogStream::Stderr,
+                    _ => return None,
+                };
+                Some(TimestampedLine {
+                    time: UNIX_EPOCH + Duration::from_micros(micros.parse().ok()?),
+                    line: LogLine {
+                        stream,
+                        line: line.to_string(),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Waits until a line matching `pattern` is logged to stdout or stderr and returns it.
+    ///
+    /// Lines logged before this call are included. Returns [`crate::error::WaitError::LogLineTimeout`] if no
+    /// matching line is logged within `timeout`.
+    fn wait_for_log_line(&self, pattern: &Regex, timeout: Duration) -> Result<LogLine, WaitError> {
+        let mut backoff = Backoff::new(timeout);
+        // only the lines logged since the previous poll are read and matched
+        let mut cursor = LogCursor::default();
+        loop {
+            if let Some(line) = self
+                .lines_since(&mut cursor)
+                .into_iter()
+                .find(|line| pattern.is_match(&line.line))
+            {
+                return Ok(line);
+            }
+            if !backoff.wait() {
+                return Err(WaitError::LogLineTimeout {
+                    pattern: pattern.to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// Appends `line` read from `stream` at `time` to a [`TIMESTAMPED_LOG`].
+pub(crate) fn write_timestamped(
+    log: &mut impl Write,
+    time: SystemTime,
+    stream: LogStream,
+    line: &str,
+) -> std::io::Result<()> {
+    let micros = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    log.write_all(format!("{micros} {stream} {line}\n").as_bytes())
+}
+
+/// Returns the lines logged by all `processes` merged into a single timeline, ordered by the time each line was
+/// captured.
+///
+/// Each line is formatted as `<UTC timestamp> <process label> <stream>: <line>`.
+pub fn timeline(processes: &[&dyn ProcessLogs]) -> String {
+    let mut lines: Vec<(SystemTime, String)> = processes
+        .iter()
+        .flat_map(|process| {
+            let label = process.process_label();
+            process
+                .timestamped_lines()
+                .into_iter()
+                .map(move |TimestampedLine { time, line }| {
+                    (
+                        time,
+                        format!(
+                            "{} {label} {}: {}",
+                            format_time(time),
+                            line.stream,
+                            line.line
+                        ),
+                    )
+                })
+        })
+        .collect();
+    // stable sort keeps the capture order of lines with equal timestamps
+    lines.sort_by_key(|(time, _)| *time);
+    lines.into_iter().map(|(_, line)| line + "\n").collect()
+}
+
+/// Writes the merged [`timeline`] of all `processes` to [`TIMELINE_LOG`] in `logs_dir` and returns it.
+///
+/// Use the logs directory of one of the processes, e.g. the validator's, to keep the timeline with its logs and
+/// artifacts.
+pub fn write_timeline(processes: &[&dyn ProcessLogs], logs_dir: &Path) -> std::io::Result<String> {
+    let timeline = timeline(processes);
+    std::fs::write(logs_dir.join(TIMELINE_LOG), &timeline)?;
+    Ok(timeline)
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with microsecond precision.
+fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_micros()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use tempfile::TempDir;
+
+    use crate::error::WaitError;
+
+    use super::{LogLine, LogStream, ProcessLogs, Regex, TIMESTAMPED_LOG};
+
+    struct TestLogs(TempDir);
+
+    impl ProcessLogs for TestLogs {
+        fn logs_path(&self) -> &Path {
+            self.0.path()
+        }
+
+        fn process_label(&self) -> String {
+            "test".to_string()
+        }
+    }
+
+    impl TestLogs {
+        fn append(&self, stream: LogStream, text: &str) {
+            std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(self.log_path(stream))
+                .unwrap()
+                .write_all(text.as_bytes())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn read_logs() {
+        let logs = TestLogs(tempfile::tempdir().unwrap());
+        logs.append(LogStream::Stdout, "starting\nsynced to height 1\n");
+        logs.append(LogStream::Stderr, "warning: slow\n");
+
+        assert_eq!(logs.stdout(), "starting\nsynced to height 1\n");
+        assert_eq!(logs.tail(LogStream::Stdout, 1), vec!["synced to height 1"]);
+        let height_pattern = Regex::new(r"height \d+").unwrap();
+        assert_eq!(
+            logs.grep(&height_pattern),
+            vec![LogLine {
+                stream: LogStream::Stdout,
+                line: "synced to height 1".to_string(),
+            }]
+        );
+
+        let mut cursor = logs.log_cursor();
+        logs.append(LogStream::Stderr, "reorg detected\npartial");
+        assert_eq!(
+            logs.lines_since(&mut cursor),
+            vec![LogLine {
+                stream: LogStream::Stderr,
+                line: "reorg detected".to_string(),
+            }]
+        );
+        logs.append(LogStream::Stderr, " line\n");
+        assert_eq!(logs.lines_since(&mut cursor)[0].line, "partial line");
+        assert!(logs.lines_since(&mut cursor).is_empty());
+
+        assert_eq!(
+            logs.wait_for_log_line(&Regex::new("reorg").unwrap(), Duration::from_secs(1))
+                .unwrap()
+                .line,
+            "reorg detected"
+        );
+        assert!(matches!(
+            logs.wait_for_log_line(&Regex::new("shutdown").unwrap(), Duration::from_millis(50)),
+            Err(WaitError::LogLineTimeout { .. })
+        ));
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(100));
+                logs.append(LogStream::Stdout, "shutdown ");
+                std::thread::sleep(Duration::from_millis(100));
+                logs.append(LogStream::Stdout, "complete\n");
+            });
+            assert_eq!(
+                logs.wait_for_log_line(&Regex::new("shutdown").unwrap(), Duration::from_secs(5))
+                    .unwrap()
+                    .line,
+                "shutdown complete"
+            );
+        });
+    }
+
+    #[test]
+    fn timeline() {
+        let validator_logs = tempfile::tempdir().unwrap();
+        std::fs::write(
+            validator_logs.path().join(TIMESTAMPED_LOG),
+            "1700000000000000 stdout block 1\n1700000000500000 stderr block 2\n",
+        )
+        .unwrap();
+        let indexer_logs = tempfile::tempdir().unwrap();
+        std::fs::write(
+            indexer_logs.path().join(TIMESTAMPED_LOG),
+            "1700000000250000 stdout synced 1\n",
+        )
+        .unwrap();
+
+        struct Labelled<'a>(&'a Path, &'a str);
+        impl ProcessLogs for Labelled<'_> {
+            fn logs_path(&self) -> &Path {
+                self.0
+            }
+
+            fn process_label(&self) -> String {
+                self.1.to_string()
+            }
+        }
+        let validator = Labelled(validator_logs.path(), "zcashd[1]");
+        let indexer = Labelled(indexer_logs.path(), "zainod[2]");
+
+        assert_eq!(
+            validator.timestamped_lines()[1].time,
+            UNIX_EPOCH + Duration::from_micros(1_700_000_000_500_000)
+        );
+        let timeline =
+            super::write_timeline(&[&validator, &indexer], validator_logs.path()).unwrap();
+        assert_eq!(
+            timeline,
+            "\
+2023-11-14T22:13:20.000000Z zcashd[1] stdout: block 1
+2023-11-14T22:13:20.250000Z zainod[2] stdout: synced 1
+2023-11-14T22:13:20.500000Z zcashd[1] stderr: block 2
+"
+        );
+        assert_eq!(
+            std::fs::read_to_string(validator_logs.path().join(super::TIMELINE_LOG)).unwrap(),
+            timeline
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Module for the connection manifest describing the processes of a running network
+//!
+//! Launches with [`crate::LaunchOptions::manifest`] set add an entry for their process to the manifest file, which is
+//! removed again when the handle is dropped. Other tools, e.g. wallet apps or scripts in other languages, read the
+//! JSON file to connect to the network. Rust code can attach to a running network with [`Manifest::attach`] and get
+//! handles to its processes with [`Manifest::validator`] and [`Manifest::indexers`].
+//!
+//! Example manifest:
+//! ```json
+//! {
+//!   "processes": [
+//!     {
+//!       "name": "zcashd",
+//!       "pid": 1234,
+//!       "start_time": 56789,
+//!       "ports": [18232, 18233],
+//!       "rpc": { "port": 18232, "user": "xxxxxx", "password": "xxxxxx" },
+//!       "grpc_uri": null,
+//!       "config_path": "/tmp/.tmpA/zcash.conf",
+//!       "data_dir": "/tmp/.tmpB",
+//!       "logs_dir": "/tmp/.tmpC",
+//!       "activation_heights": { "overwinter": 1, "sapling": 1, "blossom": 1, "heartwood": 1, "canopy": 1, "nu5": 1 }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Entries are written by the launching process, so processes launched concurrently by different test processes
+//! should use separate manifests.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use portpicker::Port;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attached::{AttachedZainod, AttachedZcashd},
+    error::{LaunchError, ManifestError},
+    logs::ProcessLogs,
+    network::ActivationHeights,
+    orphans,
+    rpc::RpcCredentials,
+    Zainod, Zcashd,
+};
+
+/// Serializes updates to manifest files by this process
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Processes of a running network
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Processes in the order they were launched
+    pub processes: Vec<ProcessManifest>,
+}
+
+/// Manifest entry of a process
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessManifest {
+    /// Process name, e.g. "zcashd"
+    pub name: String,
+    /// Process id
+    pub pid: u32,
+    /// Start time of the process, telling it apart from a later process reusing the pid, see
+    /// [`crate::orphans::start_time`]
+    pub start_time: Option<u64>,
+    /// Ports the process listens on
+    pub ports: Vec<Port>,
+    /// JSON-RPC endpoint, for validators
+    pub rpc: Option<RpcEndpoint>,
+    /// URI of the `CompactTxStreamer` gRPC service, for indexers
+    pub grpc_uri: Option<String>,
+    /// Path to the config file
+    pub config_path: Option<PathBuf>,
+    /// Data directory
+    pub data_dir: Option<PathBuf>,
+    /// Logs directory
+    pub logs_dir: PathBuf,
+    /// Network upgrade activation heights, for validators
+    pub activation_heights: Option<ManifestActivationHeights>,
+}
+
+impl ProcessLogs for ProcessManifest {
+    fn logs_path(&self) -> &Path {
+        &self.logs_dir
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", self.name, self.pid)
+    }
+}
+
+/// JSON-RPC endpoint of a validator
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcEndpoint {
+    /// RPC port on localhost
+    pub port: Port,
+    /// RPC username
+    pub user: String,
+    /// RPC password
+    pub password: String,
+}
+
+impl RpcEndpoint {
+    /// Returns the endpoint of a validator launched by this crate listening on `port`.
+    pub(crate) fn local(port: Port) -> Self {
+        let credentials = RpcCredentials::default();
+        RpcEndpoint {
+            port,
+            user: credentials.user,
+            password: credentials.password,
+        }
+    }
+}
+
+/// Network upgrade activation heights as plain block heights
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestActivationHeights {
+    /// Overwinter network upgrade activation height
+    pub overwinter: u32,
+    /// Sapling network upgrade activation height
+    pub sapling: u32,
+    /// Blossom network upgrade activation height
+    pub blossom: u32,
+    /// Heartwood network upgrade activation height
+    pub heartwood: u32,
+    /// Canopy network upgrade activation height
+    pub canopy: u32,
+    /// Nu5 (a.k.a. Orchard) network upgrade activation height
+    pub nu5: u32,
+}
+
+impl From<&ActivationHeights> for ManifestActivationHeights {
+    fn from(heights: &ActivationHeights) -> Self {
+        ManifestActivationHeights {
+            overwinter: heights.overwinter.into(),
+            sapling: heights.sapling.into(),
+            blossom: heights.blossom.into(),
+            heartwood: heights.heartwood.into(),
+            canopy: heights.canopy.into(),
+            nu5: heights.nu5.into(),
+        }
+    }
+}
+
+impl From<ManifestActivationHeights> for ActivationHeights {
+    fn from(heights: ManifestActivationHeights) -> Self {
+        ActivationHeights {
+            overwinter: heights.overwinter.into(),
+            sapling: heights.sapling.into(),
+            blossom: heights.blossom.into(),
+            heartwood: heights.heartwood.into(),
+            canopy: heights.canopy.into(),
+            nu5: heights.nu5.into(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Reads the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Manifest, ManifestError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ManifestError::Read {
+            path: path.display().to_string(),
+            source,
+        })?;
+        serde_json::from_str(&contents).map_err(|source| ManifestError::Parse {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+
+    /// Reads the manifest at `path` and checks that all of its processes are running.
+    ///
+    /// Returns [`crate::error::ManifestError::NotRunning`] for the first process which has exited, including processes
+    /// whose pid was reused by another process if their start time is recorded.
+    pub fn attach(path: &Path) -> Result<Manifest, ManifestError> {
+        let manifest = Manifest::load(path)?;
+        if let Some(process) = manifest
+            .processes
+            .iter()
+            .find(|process| !orphans::is_running(process.pid, process.start_time))
+        {
+            return Err(ManifestError::NotRunning {
+                process_name: process.name.clone(),
+                pid: process.pid,
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Returns the first process named `name`.
+    pub fn process(&self, name: &str) -> Option<&ProcessManifest> {
+        self.processes.iter().find(|process| process.name == name)
+    }
+
+    /// Attaches to the first process serving JSON-RPC, see [`crate::Zcashd::attach`].
+    ///
+    /// Returns `Ok(None)` if no process serves JSON-RPC. The logs of the process are read from its entry, see
+    /// [`Manifest::process`].
+    pub fn validator(&self) -> Result<Option<AttachedZcashd>, LaunchError> {
+        let Some((process, rpc)) = self
+            .processes
+            .iter()
+            .find_map(|process| process.rpc.as_ref().map(|rpc| (process, rpc)))
+        else {
+            return Ok(None);
+        };
+        let credentials = RpcCredentials {
+            user: rpc.user.clone(),
+            password: rpc.password.clone(),
+        };
+        Zcashd::attach(rpc.port, credentials, process.attach_config_path(rpc.port)?).map(Some)
+    }
+
+    /// Attaches to the processes serving the `CompactTxStreamer` gRPC service, see [`crate::Zainod::attach`].
+    pub fn indexers(&self) -> Result<Vec<AttachedZainod>, LaunchError> {
+        self.processes
+            .iter()
+            .filter(|process| process.grpc_uri.is_some())
+            .filter_map(|process| process.ports.first().map(|port| (process, *port)))
+            .map(|(process, port)| Zainod::attach(port, process.attach_config_path(port)?))
+            .collect()
+    }
+}
+
+impl ProcessManifest {
+    /// Returns the config path required to attach to the process listening on `port`.
+    fn attach_config_path(&self, port: Port) -> Result<PathBuf, LaunchError> {
+        self.config_path
+            .clone()
+            .ok_or_else(|| LaunchError::AttachFailed {
+                process_name: self.name.clone(),
+                port,
+                message: "manifest entry has no config path".to_string(),
+            })
+    }
+}
+
+/// Manifest entry written on launch, which is removed from the manifest when dropped.
+pub(crate) struct ManifestEntry {
+    path: PathBuf,
+    pid: u32,
+}
+
+impl ManifestEntry {
+    /// Adds `process` to the manifest at `path`, if specified. Failures are logged, as the launched process is
+    /// usable without its entry.
+    pub(crate) fn register(path: Option<&Path>, process: ProcessManifest) -> Option<ManifestEntry> {
+        let path = path?;
+        let pid = process.pid;
+        let result = update(path, |manifest| {
+            manifest.processes.retain(|entry| entry.pid != pid);
+            manifest.processes.push(process);
+        });
+        match result {
+            Ok(()) => Some(ManifestEntry {
+                path: path.to_path_buf(),
+                pid,
+            }),
+            Err(e) => {
+                tracing::warn!("failed to write manifest {}: {e}", path.display());
+                None
+            }
+        }
+    }
+}
+
+impl Drop for ManifestEntry {
+    fn drop(&mut self) {
+        let pid = self.pid;
+        if let Err(e) = update(&self.path, |manifest| {
+            manifest.processes.retain(|entry| entry.pid != pid)
+        }) {
+            tracing::warn!("failed to update manifest {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Applies `change` to the manifest at `path`, which is created if it does not exist and removed once empty.
+///
+/// The manifest is written through a temporary file, so readers never see a partially written manifest.
+fn update(path: &Path, change: impl FnOnce(&mut Manifest)) -> Result<(), ManifestError> {
+    let _lock = MANIFEST_LOCK
+        .lock()
+        .expect("manifest lock should not be poisoned");
+    let mut manifest = match Manifest::load(path) {
+        Ok(manifest) => manifest,
+        Err(ManifestError::Read { source, .. })
+            if source.kind() == std::io::ErrorKind::NotFound =>
+        {
+            Manifest::default()
+        }
+        Err(e) => return Err(e),
+    };
+    change(&mut manifest);
+
+    let write_error = |source| ManifestError::Write {
+        path: path.display().to_string(),
+        source,
+    };
+    if manifest.processes.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(write_error(e)),
+            _ => Ok(()),
+        };
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(write_error)?;
+    }
+    let contents = serde_json::to_string_pretty(&manifest).expect("manifest should serialize");
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    std::fs::write(&temp_path, contents)
+        .and_then(|()| std::fs::rename(&temp_path, path))
+        .map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use crate::{
+        error::ManifestError, mock::MockValidator, network::ActivationHeights, orphans, Indexer,
+        Validator,
+    };
+
+    use super::{Manifest, ManifestEntry, ProcessManifest, RpcEndpoint};
+
+    #[test]
+    fn register_and_attach() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("network").join("manifest.json");
+        let mock_validator = MockValidator::default();
+        let rpc_port = *mock_validator.port();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let indexer_port = listener.local_addr().unwrap().port();
+
+        let validator = ManifestEntry::register(
+            Some(&path),
+            ProcessManifest {
+                name: "zcashd".to_string(),
+                pid: std::process::id(),
+                start_time: orphans::start_time(std::process::id()),
+                ports: vec![rpc_port],
+                rpc: Some(RpcEndpoint::local(rpc_port)),
+                config_path: Some(dir.path().join("zcash.conf")),
+                logs_dir: PathBuf::from("logs"),
+                activation_heights: Some((&ActivationHeights::default()).into()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let indexer = ManifestEntry::register(
+            Some(&path),
+            ProcessManifest {
+                name: "zainod".to_string(),
+                pid: i32::MAX as u32,
+                ports: vec![indexer_port],
+                grpc_uri: Some(format!("http://127.0.0.1:{indexer_port}")),
+                config_path: Some(dir.path().join("zainod.toml")),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let manifest = Manifest::load(&path).unwrap();
+        assert_eq!(manifest.processes.len(), 2);
+        let validator_handle = manifest.validator().unwrap().unwrap();
+        assert_eq!(validator_handle.rpc_port(), rpc_port);
+        mock_validator.chain().generate_blocks(2);
+        validator_handle
+            .wait_for_height(1, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(
+            manifest.process("zcashd").unwrap().activation_heights,
+            Some((&ActivationHeights::default()).into())
+        );
+        assert_eq!(manifest.indexers().unwrap()[0].listen_port(), indexer_port);
+        assert!(matches!(
+            Manifest::attach(&path),
+            Err(ManifestError::NotRunning { pid, .. }) if pid == i32::MAX as u32
+        ));
+
+        drop(indexer);
+        let manifest = Manifest::attach(&path).unwrap();
+        assert_eq!(manifest.processes.len(), 1);
+
+        // a process reusing the pid has a different start time
+        #[cfg(target_os = "linux")]
+        {
+            let reused_path = dir.path().join("reused.json");
+            let _reused = ManifestEntry::register(
+                Some(&reused_path),
+                ProcessManifest {
+                    name: "zainod".to_string(),
+                    pid: std::process::id(),
+                    start_time: orphans::start_time(std::process::id()).map(|time| time + 1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert!(matches!(
+                Manifest::attach(&reused_path),
+                Err(ManifestError::NotRunning { .. })
+            ));
+        }
+
+        drop(validator);
+        assert!(!path.exists());
+        assert!(matches!(
+            Manifest::load(&path),
+            Err(ManifestError::Read { .. })
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+//! Module for matching process log lines, e.g. to detect that a process is ready or failed to launch
+
+use crate::logs::{LogStream, Regex};
+
+/// Matcher for process log lines.
+///
+/// Example usage for waiting until both the RPC and gRPC servers of a process are ready:
+/// ```ignore (incomplete)
+/// let ready = Matcher::all(vec![
+///     Matcher::contains("RPC server ready"),
+///     Matcher::regex(r"gRPC server listening on \d+")?.on_stream(LogStream::Stdout),
+/// ]);
+/// ```
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// Matches lines containing the substring
+    Contains(String),
+    /// Matches lines matching the regex
+    Regex(Regex),
+    /// Applies the matcher to the lines of the stream only
+    Stream(LogStream, Box<Matcher>),
+    /// Matches once any of the matchers matched
+    Any(Vec<Matcher>),
+    /// Matches once all of the matchers matched, not necessarily on the same line.
+    ///
+    /// [`Matcher::is_match`], which checks a single line, e.g. for [`crate::LaunchOptions::ignored_failures`],
+    /// requires all of the matchers to match that line.
+    All(Vec<Matcher>),
+}
+
+impl Matcher {
+    /// Returns a matcher for lines containing `substring`.
+    pub fn contains(substring: &str) -> Self {
+        Matcher::Contains(substring.to_string())
+    }
+
+    /// Returns a matcher for lines matching the regex `pattern`.
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Matcher::Regex(Regex::new(pattern)?))
+    }
+
+    /// Returns a matcher that matches once any of `matchers` matched.
+    pub fn any(matchers: Vec<Matcher>) -> Self {
+        Matcher::Any(matchers)
+    }
+
+    /// Returns a matcher that matches once all of `matchers` matched.
+    pub fn all(matchers: Vec<Matcher>) -> Self {
+        Matcher::All(matchers)
+    }
+
+    /// Restricts the matcher to the lines of `stream`.
+    pub fn on_stream(self, stream: LogStream) -> Self {
+        Matcher::Stream(stream, Box::new(self))
+    }
+
+    /// Returns whether a single `line` read from `stream` is matched.
+    ///
+    /// [`Matcher::All`] matches only if all of its matchers match the same line.
+    pub fn is_match(&self, stream: LogStream, line: &str) -> bool {
+        match self {
+            Matcher::Contains(substring) => line.contains(substring.as_str()),
+            Matcher::Regex(regex) => regex.is_match(line),
+            Matcher::Stream(matcher_stream, matcher) => {
+                *matcher_stream == stream && matcher.is_match(stream, line)
+            }
+            Matcher::Any(matchers) => matchers
+                .iter()
+                .any(|matcher| matcher.is_match(stream, line)),
+            Matcher::All(matchers) => matchers
+                .iter()
+                .all(|matcher| matcher.is_match(stream, line)),
+        }
+    }
+}
+
+/// State of a [`Matcher`] fed with the lines of a process in order.
+pub(crate) struct MatchState<'a> {
+    matcher: &'a Matcher,
+    matched: bool,
+    children: Vec<MatchState<'a>>,
+}
+
+impl<'a> MatchState<'a> {
+    pub(crate) fn new(matcher: &'a Matcher) -> Self {
+        let children = match matcher {
+            Matcher::Stream(_, matcher) => vec![MatchState::new(matcher)],
+            Matcher::Any(matchers) | Matcher::All(matchers) => {
+                matchers.iter().map(MatchState::new).collect()
+            }
+            Matcher::Contains(_) | Matcher::Regex(_) => Vec::new(),
+        };
+        MatchState {
+            matcher,
+            matched: false,
+            children,
+        }
+    }
+
+    /// Feeds the next `line` read from `stream` and returns whether the matcher has matched.
+    pub(crate) fn feed(&mut self, stream: LogStream, line: &str) -> bool {
+        if self.matched {
+            return true;
+        }
+        self.matched = match self.matcher {
+            Matcher::Contains(_) | Matcher::Regex(_) => self.matcher.is_match(stream, line),
+            Matcher::Stream(matcher_stream, _) => {
+                *matcher_stream == stream && self.children[0].feed(stream, line)
+            }
+            Matcher::Any(_) | Matcher::All(_) => {
+                // every child is fed so that nested `All` matchers see each line
+                let children_matched: Vec<bool> = self
+                    .children
+                    .iter_mut()
+                    .map(|child| child.feed(stream, line))
+                    .collect();
+                if matches!(self.matcher, Matcher::Any(_)) {
+                    children_matched.contains(&true)
+                } else {
+                    !children_matched.contains(&false)
+                }
+            }
+        };
+        self.matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logs::LogStream;
+
+    use super::{MatchState, Matcher};
+
+    #[test]
+    fn matchers() {
+        let ready = Matcher::all(vec![
+            Matcher::contains("RPC ready").on_stream(LogStream::Stdout),
+            Matcher::regex(r"listening on \d+").unwrap(),
+        ]);
+        let mut state = MatchState::new(&ready);
+        assert!(!state.feed(LogStream::Stderr, "RPC ready"));
+        assert!(!state.feed(LogStream::Stdout, "RPC ready"));
+        assert!(!state.feed(LogStream::Stdout, "listening on port"));
+        assert!(state.feed(LogStream::Stderr, "listening on 8232"));
+        assert!(state.feed(LogStream::Stdout, "anything"));
+
+        let failure = Matcher::any(vec![
+            Matcher::contains("Error:"),
+            Matcher::regex("(?i)fatal").unwrap(),
+        ]);
+        assert!(failure.is_match(LogStream::Stderr, "FATAL: bad config"));
+        assert!(!failure.is_match(LogStream::Stderr, "warning"));
+        assert!(!ready.is_match(LogStream::Stdout, "RPC ready"));
+        assert!(ready.is_match(LogStream::Stdout, "RPC ready, listening on 8232"));
+    }
+}
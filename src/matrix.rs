@@ -0,0 +1,164 @@
+//! Module for running a test against each combination of validator and indexer versions
+//!
+//! The versions are taken from a [`crate::binaries::BinariesDir`].
+//!
+//! Example usage:
+//! ```ignore (incomplete)
+//! let binaries_dir = BinariesDir::from_env().expect("binaries directory should be set");
+//! run_matrix(&binaries_dir, Binary::Zcashd, Binary::Zainod, |case| {
+//!     let zcashd = Zcashd::launch(
+//!         Some(case.validator.path.clone()),
+//!         case.validator.companion(Binary::ZcashCli),
+//!         None,
+//!         &ActivationHeights::default(),
+//!         None,
+//!     )
+//!     .unwrap();
+//!     let zainod = Zainod::launch(Some(case.indexer.path.clone()), None, *zcashd.port()).unwrap();
+//!     // test the wallet against zcashd and zainod
+//! })
+//! .unwrap()
+//! .assert_passed();
+//! ```
+
+use std::panic::AssertUnwindSafe;
+
+use crate::binaries::{BinariesDir, Binary, VersionedBinary};
+
+/// Combination of validator and indexer versions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatrixCase {
+    /// Validator version
+    pub validator: VersionedBinary,
+    /// Indexer version
+    pub indexer: VersionedBinary,
+}
+
+impl std::fmt::Display for MatrixCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} / {}", self.validator, self.indexer)
+    }
+}
+
+/// Combination for which the test panicked
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatrixFailure {
+    /// Failed combination
+    pub case: MatrixCase,
+    /// Panic message
+    pub message: String,
+}
+
+/// Results of [`run_matrix`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatrixReport {
+    /// Combinations for which the test passed
+    pub passed: Vec<MatrixCase>,
+    /// Combinations for which the test panicked
+    pub failed: Vec<MatrixFailure>,
+}
+
+impl MatrixReport {
+    /// Panics listing the failed combinations if any combination failed or no combination was run.
+    pub fn assert_passed(&self) {
+        if self.passed.is_empty() && self.failed.is_empty() {
+            panic!("no validator and indexer version combinations were found");
+        }
+        if !self.failed.is_empty() {
+            let failures: String = self
+                .failed
+                .iter()
+                .map(|failure| format!("\n{}: {}", failure.case, failure.message))
+                .collect();
+            panic!(
+                "{} of {} version combinations failed:{failures}",
+                self.failed.len(),
+                self.failed.len() + self.passed.len()
+            );
+        }
+    }
+}
+
+/// Runs `test` once per combination of the `validator` and `indexer` versions available in `binaries_dir`.
+///
+/// Combinations are run one after another, oldest validator first. A combination fails if `test` panics, in which
+/// case the remaining combinations are still run.
+pub fn run_matrix<F>(
+    binaries_dir: &BinariesDir,
+    validator: Binary,
+    indexer: Binary,
+    test: F,
+) -> std::io::Result<MatrixReport>
+where
+    F: Fn(&MatrixCase),
+{
+    let indexers = binaries_dir.versions(indexer)?;
+    let mut report = MatrixReport::default();
+    for validator in binaries_dir.versions(validator)? {
+        for indexer in &indexers {
+            let case = MatrixCase {
+                validator: validator.clone(),
+                indexer: indexer.clone(),
+            };
+            tracing::info!("running version combination {case}");
+            match std::panic::catch_unwind(AssertUnwindSafe(|| test(&case))) {
+                Ok(()) => report.passed.push(case),
+                Err(payload) => {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "non-string panic payload".to_string());
+                    report.failed.push(MatrixFailure { case, message });
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+// the tests write executable scripts
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::binaries::{BinariesDir, Binary, Version};
+
+    #[test]
+    fn run_matrix() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, version) in [
+            ("zcashd", "5.10.0"),
+            ("zcashd", "6.1.0"),
+            ("zainod", "0.1.1"),
+            ("zainod", "0.1.2"),
+        ] {
+            let version_dir = dir.path().join(name).join(version);
+            std::fs::create_dir_all(&version_dir).unwrap();
+            let path = version_dir.join(name);
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let report = super::run_matrix(
+            &BinariesDir::new(dir.path()),
+            Binary::Zcashd,
+            Binary::Zainod,
+            |case| {
+                assert!(
+                    case.validator.version != Version::new(6, 1, 0)
+                        || case.indexer.version != Version::new(0, 1, 1),
+                    "incompatible"
+                );
+            },
+        )
+        .unwrap();
+        assert_eq!(report.passed.len(), 3);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(
+            report.failed[0].case.to_string(),
+            "zcashd 6.1.0 / zainod 0.1.1"
+        );
+        assert_eq!(report.failed[0].message, "incompatible");
+    }
+}
@@ -0,0 +1,10 @@
+//! In-process stand-ins for the local net daemons
+//!
+//! Mocks serve scripted data on localhost ports and can be used in place of the daemons they replace, so that tests
+//! can run without the daemon binaries installed.
+
+mod indexer;
+mod validator;
+
+pub use indexer::{MockCompactChain, MockIndexer};
+pub use validator::{MockBlock, MockChain, MockTransaction, MockUtxo, MockValidator};
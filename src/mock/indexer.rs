@@ -0,0 +1,724 @@
+//! Mock indexer serving the light wallet `CompactTxStreamer` gRPC service from an in-memory chain
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use getset::Getters;
+use portpicker::Port;
+use tokio::sync::oneshot;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::network::{self, ActivationHeights};
+use crate::proto::{
+    compact_tx_streamer_server::{CompactTxStreamer, CompactTxStreamerServer},
+    Address, AddressList, Balance, BlockId, BlockRange, ChainSpec, CompactBlock, CompactTx,
+    Duration, Empty, Exclude, GetAddressUtxosArg, GetAddressUtxosReply, GetAddressUtxosReplyList,
+    GetSubtreeRootsArg, LightdInfo, PingResponse, RawTransaction, SendResponse, ShieldedProtocol,
+    SubtreeRoot, TransparentAddressBlockFilter, TreeState, TxFilter,
+};
+use crate::Indexer;
+
+/// In-memory chain backing the [`MockIndexer`].
+///
+/// The test scripts the chain by pushing compact blocks, truncating it to simulate reorgs and adding tree states,
+/// transactions and transparent outputs. Changes are visible to the next gRPC request.
+#[derive(Clone, Debug, Default)]
+pub struct MockCompactChain {
+    activation_heights: ActivationHeights,
+    blocks: Vec<CompactBlock>,
+    tree_states: Vec<TreeState>,
+    transactions: Vec<(Vec<u8>, RawTransaction)>,
+    mempool: Vec<(CompactTx, RawTransaction)>,
+    sapling_subtree_roots: Vec<SubtreeRoot>,
+    orchard_subtree_roots: Vec<SubtreeRoot>,
+    utxos: Vec<GetAddressUtxosReply>,
+    sent_transactions: Vec<RawTransaction>,
+}
+
+impl MockCompactChain {
+    /// Creates an empty chain with the given network upgrade activation heights.
+    pub fn new(activation_heights: ActivationHeights) -> Self {
+        MockCompactChain {
+            activation_heights,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the height of the chain tip or `None` if the chain is empty.
+    pub fn height(&self) -> Option<u64> {
+        self.blocks.last().map(|block| block.height)
+    }
+
+    /// Returns the compact blocks in the chain, in ascending height order.
+    pub fn blocks(&self) -> &[CompactBlock] {
+        &self.blocks
+    }
+
+    /// Appends `block` to the chain.
+    ///
+    /// Panics if the block height does not extend the chain tip.
+    /// Transactions in the block are removed from the mempool.
+    pub fn push_block(&mut self, block: CompactBlock) {
+        if let Some(height) = self.height() {
+            assert_eq!(block.height, height + 1, "block does not extend chain tip");
+        }
+        self.mempool
+            .retain(|(tx, _)| !block.vtx.iter().any(|mined| mined.hash == tx.hash));
+        self.blocks.push(block);
+    }
+
+    /// Removes all blocks and tree states above `height`, returning their transactions to the mempool.
+    ///
+    /// The full transactions added with [`MockCompactChain::add_transaction`] are moved to the mempool with their
+    /// compact transactions, or empty ones if they were not added. Push replacement blocks afterwards to simulate a
+    /// reorg.
+    pub fn truncate(&mut self, height: u64) {
+        let removed = self
+            .blocks
+            .split_off(self.blocks.partition_point(|block| block.height <= height));
+        let (removed_transactions, transactions) = std::mem::take(&mut self.transactions)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, tx)| tx.height > height);
+        self.transactions = transactions;
+        for compact in removed.into_iter().flat_map(|block| block.vtx) {
+            let transaction = removed_transactions
+                .iter()
+                .find(|(txid, _)| *txid == compact.hash)
+                .map(|(_, tx)| RawTransaction {
+                    data: tx.data.clone(),
+                    height: 0,
+                })
+                .unwrap_or_default();
+            self.mempool.push((compact, transaction));
+        }
+        self.tree_states
+            .retain(|tree_state| tree_state.height <= height);
+        self.utxos.retain(|utxo| utxo.height <= height);
+    }
+
+    /// Adds the note commitment tree state served by `GetTreeState` for the block at `tree_state.height`.
+    pub fn add_tree_state(&mut self, tree_state: TreeState) {
+        self.tree_states
+            .retain(|existing| existing.height != tree_state.height);
+        self.tree_states.push(tree_state);
+    }
+
+    /// Adds a mined transaction served by `GetTransaction`.
+    pub fn add_transaction(&mut self, txid: Vec<u8>, transaction: RawTransaction) {
+        self.transactions.push((txid, transaction));
+    }
+
+    /// Adds a transaction to the mempool, served by `GetMempoolTx` and `GetMempoolStream`.
+    pub fn add_mempool_transaction(&mut self, compact: CompactTx, transaction: RawTransaction) {
+        self.mempool.push((compact, transaction));
+    }
+
+    /// Adds a subtree root served by `GetSubtreeRoots`.
+    pub fn add_subtree_root(&mut self, protocol: ShieldedProtocol, root: SubtreeRoot) {
+        match protocol {
+            ShieldedProtocol::Sapling => self.sapling_subtree_roots.push(root),
+            ShieldedProtocol::Orchard => self.orchard_subtree_roots.push(root),
+        }
+    }
+
+    /// Adds a transparent output served by `GetAddressUtxos`.
+    pub fn add_utxo(&mut self, utxo: GetAddressUtxosReply) {
+        self.utxos.push(utxo);
+    }
+
+    /// Returns the transactions submitted with `SendTransaction`.
+    pub fn sent_transactions(&self) -> &[RawTransaction] {
+        &self.sent_transactions
+    }
+
+    fn block(&self, block_id: &BlockId) -> Option<&CompactBlock> {
+        self.blocks.iter().find(|block| {
+            if block_id.hash.is_empty() {
+                block.height == block_id.height
+            } else {
+                block.hash == block_id.hash
+            }
+        })
+    }
+
+    /// Returns the blocks from `start` to `end` inclusive, in descending order if `start` is above `end`.
+    fn block_range(&self, start: u64, end: u64) -> Vec<CompactBlock> {
+        let mut blocks: Vec<CompactBlock> = self
+            .blocks
+            .iter()
+            .filter(|block| (start.min(end)..=start.max(end)).contains(&block.height))
+            .cloned()
+            .collect();
+        if start > end {
+            blocks.reverse();
+        }
+        blocks
+    }
+
+    fn lightd_info(&self) -> LightdInfo {
+        let block_height = self.height().unwrap_or_default();
+        LightdInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            vendor: "zcash-local-net mock indexer".to_string(),
+            taddr_support: true,
+            chain_name: "regtest".to_string(),
+            sapling_activation_height: u32::from(self.activation_heights.sapling) as u64,
+            consensus_branch_id: network::consensus_branch_id(
+                &self.activation_heights,
+                block_height as u32,
+            )
+            .to_string(),
+            block_height,
+            estimated_height: block_height,
+            ..Default::default()
+        }
+    }
+}
+
+fn block_not_found(block_id: &BlockId) -> Status {
+    Status::not_found(format!("block not found: {block_id:?}"))
+}
+
+/// Returns the start and end heights of a block range request.
+fn range_heights(range: &BlockRange) -> Option<(u64, u64)> {
+    Some((range.start.as_ref()?.height, range.end.as_ref()?.height))
+}
+
+fn invalid_range() -> Status {
+    Status::invalid_argument("block range must have a start and end")
+}
+
+/// Strips a compact block to the nullifiers of its spends and actions.
+fn nullifiers_only(mut block: CompactBlock) -> CompactBlock {
+    for tx in block.vtx.iter_mut() {
+        tx.outputs.clear();
+        for action in tx.actions.iter_mut() {
+            action.cmx.clear();
+            action.ephemeral_key.clear();
+            action.ciphertext.clear();
+        }
+    }
+    block
+}
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+fn stream<T: Send + 'static>(items: Vec<T>) -> ResponseStream<T> {
+    Box::pin(tokio_stream::iter(items.into_iter().map(Ok)))
+}
+
+struct MockCompactTxStreamer {
+    chain: Arc<Mutex<MockCompactChain>>,
+}
+
+impl MockCompactTxStreamer {
+    fn chain(&self) -> MutexGuard<'_, MockCompactChain> {
+        self.chain
+            .lock()
+            .expect("mock chain lock should not be poisoned")
+    }
+}
+
+#[tonic::async_trait]
+impl CompactTxStreamer for MockCompactTxStreamer {
+    async fn get_latest_block(
+        &self,
+        _request: Request<ChainSpec>,
+    ) -> Result<Response<BlockId>, Status> {
+        let chain = self.chain();
+        let tip = chain
+            .blocks
+            .last()
+            .ok_or_else(|| Status::unavailable("chain is empty"))?;
+        Ok(Response::new(BlockId {
+            height: tip.height,
+            hash: tip.hash.clone(),
+        }))
+    }
+
+    async fn get_block(&self, request: Request<BlockId>) -> Result<Response<CompactBlock>, Status> {
+        let block_id = request.into_inner();
+        let block = self
+            .chain()
+            .block(&block_id)
+            .cloned()
+            .ok_or_else(|| block_not_found(&block_id))?;
+        Ok(Response::new(block))
+    }
+
+    async fn get_block_nullifiers(
+        &self,
+        request: Request<BlockId>,
+    ) -> Result<Response<CompactBlock>, Status> {
+        let block_id = request.into_inner();
+        let block = self
+            .chain()
+            .block(&block_id)
+            .cloned()
+            .ok_or_else(|| block_not_found(&block_id))?;
+        Ok(Response::new(nullifiers_only(block)))
+    }
+
+    type GetBlockRangeStream = ResponseStream<CompactBlock>;
+
+    async fn get_block_range(
+        &self,
+        request: Request<BlockRange>,
+    ) -> Result<Response<Self::GetBlockRangeStream>, Status> {
+        let (start, end) = range_heights(request.get_ref()).ok_or_else(invalid_range)?;
+        let blocks = self.chain().block_range(start, end);
+        Ok(Response::new(stream(blocks)))
+    }
+
+    type GetBlockRangeNullifiersStream = ResponseStream<CompactBlock>;
+
+    async fn get_block_range_nullifiers(
+        &self,
+        request: Request<BlockRange>,
+    ) -> Result<Response<Self::GetBlockRangeNullifiersStream>, Status> {
+        let (start, end) = range_heights(request.get_ref()).ok_or_else(invalid_range)?;
+        let blocks = self.chain().block_range(start, end);
+        Ok(Response::new(stream(
+            blocks.into_iter().map(nullifiers_only).collect(),
+        )))
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<TxFilter>,
+    ) -> Result<Response<RawTransaction>, Status> {
+        let filter = request.into_inner();
+        let chain = self.chain();
+        chain
+            .transactions
+            .iter()
+            .find(|(txid, _)| *txid == filter.hash)
+            .map(|(_, tx)| tx)
+            .or_else(|| {
+                chain
+                    .mempool
+                    .iter()
+                    .find(|(compact, _)| compact.hash == filter.hash)
+                    .map(|(_, tx)| tx)
+            })
+            .cloned()
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found("transaction not found"))
+    }
+
+    async fn send_transaction(
+        &self,
+        request: Request<RawTransaction>,
+    ) -> Result<Response<SendResponse>, Status> {
+        self.chain().sent_transactions.push(request.into_inner());
+        Ok(Response::new(SendResponse {
+            error_code: 0,
+            error_message: String::new(),
+        }))
+    }
+
+    type GetTaddressTxidsStream = ResponseStream<RawTransaction>;
+
+    async fn get_taddress_txids(
+        &self,
+        request: Request<TransparentAddressBlockFilter>,
+    ) -> Result<Response<Self::GetTaddressTxidsStream>, Status> {
+        let filter = request.into_inner();
+        let (start, end) = filter.range.map_or((0, u64::MAX), |range| {
+            (
+                range.start.map_or(0, |start| start.height),
+                range.end.map_or(u64::MAX, |end| end.height),
+            )
+        });
+        let chain = self.chain();
+        let transactions = chain
+            .transactions
+            .iter()
+            .filter(|(txid, tx)| {
+                (start..=end).contains(&tx.height)
+                    && chain
+                        .utxos
+                        .iter()
+                        .any(|utxo| utxo.address == filter.address && utxo.txid == *txid)
+            })
+            .map(|(_, tx)| tx.clone())
+            .collect();
+        Ok(Response::new(stream(transactions)))
+    }
+
+    async fn get_taddress_balance(
+        &self,
+        request: Request<AddressList>,
+    ) -> Result<Response<Balance>, Status> {
+        let addresses = request.into_inner().addresses;
+        let value_zat = self
+            .chain()
+            .utxos
+            .iter()
+            .filter(|utxo| addresses.contains(&utxo.address))
+            .map(|utxo| utxo.value_zat)
+            .sum();
+        Ok(Response::new(Balance { value_zat }))
+    }
+
+    async fn get_taddress_balance_stream(
+        &self,
+        request: Request<Streaming<Address>>,
+    ) -> Result<Response<Balance>, Status> {
+        let mut addresses = Vec::new();
+        let mut stream = request.into_inner();
+        while let Some(address) = stream.message().await? {
+            addresses.push(address.address);
+        }
+        self.get_taddress_balance(Request::new(AddressList { addresses }))
+            .await
+    }
+
+    type GetMempoolTxStream = ResponseStream<CompactTx>;
+
+    async fn get_mempool_tx(
+        &self,
+        request: Request<Exclude>,
+    ) -> Result<Response<Self::GetMempoolTxStream>, Status> {
+        let exclude = request.into_inner().txid;
+        let transactions = self
+            .chain()
+            .mempool
+            .iter()
+            .map(|(compact, _)| compact)
+            .filter(|compact| {
+                !exclude
+                    .iter()
+                    .any(|prefix| compact.hash.starts_with(prefix))
+            })
+            .cloned()
+            .collect();
+        Ok(Response::new(stream(transactions)))
+    }
+
+    type GetMempoolStreamStream = ResponseStream<RawTransaction>;
+
+    /// Streams the transactions currently in the mempool and closes the stream.
+    async fn get_mempool_stream(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::GetMempoolStreamStream>, Status> {
+        let transactions = self
+            .chain()
+            .mempool
+            .iter()
+            .map(|(_, tx)| tx.clone())
+            .collect();
+        Ok(Response::new(stream(transactions)))
+    }
+
+    async fn get_tree_state(
+        &self,
+        request: Request<BlockId>,
+    ) -> Result<Response<TreeState>, Status> {
+        let block_id = request.into_inner();
+        let chain = self.chain();
+        let height = if block_id.hash.is_empty() {
+            block_id.height
+        } else {
+            chain
+                .block(&block_id)
+                .ok_or_else(|| block_not_found(&block_id))?
+                .height
+        };
+        chain
+            .tree_states
+            .iter()
+            .find(|tree_state| tree_state.height == height)
+            .cloned()
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("no tree state at height {height}")))
+    }
+
+    async fn get_latest_tree_state(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<TreeState>, Status> {
+        let height = self
+            .chain()
+            .height()
+            .ok_or_else(|| Status::unavailable("chain is empty"))?;
+        self.get_tree_state(Request::new(BlockId {
+            height,
+            hash: Vec::new(),
+        }))
+        .await
+    }
+
+    type GetSubtreeRootsStream = ResponseStream<SubtreeRoot>;
+
+    async fn get_subtree_roots(
+        &self,
+        request: Request<GetSubtreeRootsArg>,
+    ) -> Result<Response<Self::GetSubtreeRootsStream>, Status> {
+        let arg = request.into_inner();
+        let chain = self.chain();
+        let roots = match arg.shielded_protocol() {
+            ShieldedProtocol::Sapling => &chain.sapling_subtree_roots,
+            ShieldedProtocol::Orchard => &chain.orchard_subtree_roots,
+        };
+        let max_entries = match arg.max_entries {
+            0 => usize::MAX,
+            max_entries => max_entries as usize,
+        };
+        let roots = roots
+            .iter()
+            .skip(arg.start_index as usize)
+            .take(max_entries)
+            .cloned()
+            .collect();
+        Ok(Response::new(stream(roots)))
+    }
+
+    async fn get_address_utxos(
+        &self,
+        request: Request<GetAddressUtxosArg>,
+    ) -> Result<Response<GetAddressUtxosReplyList>, Status> {
+        let arg = request.into_inner();
+        let max_entries = match arg.max_entries {
+            0 => usize::MAX,
+            max_entries => max_entries as usize,
+        };
+        let mut address_utxos: Vec<GetAddressUtxosReply> = self
+            .chain()
+            .utxos
+            .iter()
+            .filter(|utxo| arg.addresses.contains(&utxo.address) && utxo.height >= arg.start_height)
+            .cloned()
+            .collect();
+        address_utxos.sort_by_key(|utxo| utxo.height);
+        address_utxos.truncate(max_entries);
+        Ok(Response::new(GetAddressUtxosReplyList { address_utxos }))
+    }
+
+    type GetAddressUtxosStreamStream = ResponseStream<GetAddressUtxosReply>;
+
+    async fn get_address_utxos_stream(
+        &self,
+        request: Request<GetAddressUtxosArg>,
+    ) -> Result<Response<Self::GetAddressUtxosStreamStream>, Status> {
+        let utxos = self.get_address_utxos(request).await?.into_inner();
+        Ok(Response::new(stream(utxos.address_utxos)))
+    }
+
+    async fn get_lightd_info(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<LightdInfo>, Status> {
+        Ok(Response::new(self.chain().lightd_info()))
+    }
+
+    async fn ping(&self, _request: Request<Duration>) -> Result<Response<PingResponse>, Status> {
+        Ok(Response::new(PingResponse { entry: 1, exit: 0 }))
+    }
+}
+
+/// This struct is used to represent and manage a mock indexer.
+///
+/// The mock indexer serves the light wallet `CompactTxStreamer` gRPC service from a [`MockCompactChain`] held in
+/// memory, so wallet sync logic can be tested without launching a validator or indexer. The server runs on its own
+/// thread and stops when dropped.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct MockIndexer {
+    /// gRPC Port
+    port: Port,
+    /// Chain served by the mock indexer
+    #[getset(skip)]
+    chain: Arc<Mutex<MockCompactChain>>,
+    /// Sends the server shutdown signal
+    #[getset(skip)]
+    shutdown: Option<oneshot::Sender<()>>,
+    /// Server thread handle
+    #[getset(skip)]
+    server_thread: Option<JoinHandle<()>>,
+}
+
+impl MockIndexer {
+    /// Launches the mock indexer serving `chain` and returns [`crate::mock::MockIndexer`].
+    ///
+    /// Use `listen_port` to specify a port for the mock indexer. Otherwise, a port is picked at random.
+    pub fn launch(
+        listen_port: Option<Port>,
+        chain: MockCompactChain,
+    ) -> std::io::Result<MockIndexer> {
+        let port = network::pick_unused_port(listen_port);
+        let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let chain = Arc::new(Mutex::new(chain));
+        let service = CompactTxStreamerServer::new(MockCompactTxStreamer {
+            chain: chain.clone(),
+        });
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+
+        let server_thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener)
+                    .expect("should be able to register listener with runtime");
+                let server = tokio::spawn(
+                    tonic::transport::Server::builder()
+                        .add_service(service)
+                        .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(
+                            listener,
+                        )),
+                );
+                // open client connections are not drained, so shutdown does not wait on clients
+                // driven by a runtime that may be blocked on this thread's join
+                let _ = shutdown_signal.await;
+                server.abort();
+            });
+        });
+
+        Ok(MockIndexer {
+            port,
+            chain,
+            shutdown: Some(shutdown),
+            server_thread: Some(server_thread),
+        })
+    }
+
+    /// Locks and returns the chain for scripting.
+    ///
+    /// Example usage for mining a block:
+    /// ```ignore (incomplete)
+    /// mock_indexer.chain().push_block(compact_block);
+    /// ```
+    pub fn chain(&self) -> MutexGuard<'_, MockCompactChain> {
+        self.chain
+            .lock()
+            .expect("mock chain lock should not be poisoned")
+    }
+
+    /// Stops the mock indexer.
+    pub fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(server_thread) = self.server_thread.take() {
+            if server_thread.join().is_err() {
+                tracing::error!("mock indexer server thread panicked");
+            }
+        }
+    }
+}
+
+impl Default for MockIndexer {
+    /// Default launch for the mock indexer with an empty chain.
+    /// Panics on failure.
+    fn default() -> Self {
+        MockIndexer::launch(None, MockCompactChain::new(ActivationHeights::default())).unwrap()
+    }
+}
+
+impl Indexer for MockIndexer {
+    fn listen_port(&self) -> Port {
+        self.port
+    }
+}
+
+impl Drop for MockIndexer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::{
+        compact_tx_streamer_client::CompactTxStreamerClient, BlockId, BlockRange, ChainSpec,
+        CompactBlock, CompactTx, Empty, RawTransaction,
+    };
+
+    use super::MockIndexer;
+
+    #[tokio::test]
+    async fn serves_scripted_blocks() {
+        let mock_indexer = MockIndexer::default();
+        for height in 1..=3 {
+            mock_indexer.chain().push_block(CompactBlock {
+                height,
+                hash: vec![height as u8; 32],
+                ..Default::default()
+            });
+        }
+
+        let mut client =
+            CompactTxStreamerClient::connect(format!("http://127.0.0.1:{}", mock_indexer.port()))
+                .await
+                .unwrap();
+
+        let latest = client.get_latest_block(ChainSpec {}).await.unwrap();
+        assert_eq!(latest.into_inner().height, 3);
+
+        let mut blocks = client
+            .get_block_range(BlockRange {
+                start: Some(BlockId {
+                    height: 3,
+                    hash: Vec::new(),
+                }),
+                end: Some(BlockId {
+                    height: 2,
+                    hash: Vec::new(),
+                }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut heights = Vec::new();
+        while let Some(block) = blocks.message().await.unwrap() {
+            heights.push(block.height);
+        }
+        assert_eq!(heights, vec![3, 2]);
+
+        let mined = CompactTx {
+            hash: vec![7; 32],
+            ..Default::default()
+        };
+        mock_indexer.chain().truncate(2);
+        mock_indexer.chain().push_block(CompactBlock {
+            height: 3,
+            hash: vec![4; 32],
+            vtx: vec![mined.clone()],
+            ..Default::default()
+        });
+        mock_indexer.chain().add_transaction(
+            mined.hash.clone(),
+            RawTransaction {
+                data: vec![7],
+                height: 3,
+            },
+        );
+        mock_indexer.chain().truncate(2);
+        assert_eq!(mock_indexer.chain().height(), Some(2));
+        let mut mempool = client
+            .get_mempool_stream(Empty {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            mempool.message().await.unwrap(),
+            Some(RawTransaction {
+                data: vec![7],
+                height: 0,
+            })
+        );
+
+        let tx = RawTransaction {
+            data: vec![1, 2, 3],
+            height: 0,
+        };
+        client.send_transaction(tx.clone()).await.unwrap();
+        assert_eq!(mock_indexer.chain().sent_transactions(), &[tx]);
+    }
+}
@@ -0,0 +1,646 @@
+//! Mock validator serving the validator JSON-RPC methods used by indexers from an in-memory chain
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use portpicker::Port;
+use serde_json::{json, Value};
+
+use crate::http::{HttpResponse, HttpServer};
+use crate::network::{self, ActivationHeights};
+use crate::Validator;
+
+const RPC_INVALID_PARAMS: i64 = -8;
+const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+const RPC_METHOD_NOT_FOUND: i64 = -32601;
+const RPC_VERIFY_REJECTED: i64 = -26;
+
+/// Transaction served by the [`MockValidator`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MockTransaction {
+    /// Transaction id (hex, RPC byte order)
+    pub txid: String,
+    /// Serialized transaction (hex)
+    pub raw: String,
+}
+
+/// Block served by the [`MockValidator`].
+///
+/// The block height is its position in the [`MockChain`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MockBlock {
+    /// Block hash (hex, RPC byte order)
+    pub hash: String,
+    /// Block time
+    pub time: u32,
+    /// Serialized block (hex), returned by `getblock` with verbosity 0
+    pub raw: String,
+    /// Transactions in the block
+    pub transactions: Vec<MockTransaction>,
+    /// Sapling note commitment tree state after this block (hex), returned by `z_gettreestate`
+    pub sapling_tree: String,
+    /// Orchard note commitment tree state after this block (hex), returned by `z_gettreestate`
+    pub orchard_tree: String,
+    /// Size of the sapling note commitment tree after this block
+    pub sapling_tree_size: u64,
+    /// Size of the orchard note commitment tree after this block
+    pub orchard_tree_size: u64,
+}
+
+/// Transparent output served by `getaddressutxos`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MockUtxo {
+    /// Transparent address
+    pub address: String,
+    /// Transaction id (hex, RPC byte order)
+    pub txid: String,
+    /// Output index
+    pub output_index: u32,
+    /// Output script (hex)
+    pub script: String,
+    /// Output value in zatoshis
+    pub satoshis: u64,
+    /// Height of the block containing the transaction
+    pub height: u32,
+}
+
+/// In-memory chain backing the [`MockValidator`].
+///
+/// The test scripts the chain by pushing blocks, truncating it to simulate reorgs and adding transactions to the
+/// mempool. Changes are visible to the next RPC request.
+#[derive(Clone, Debug, Default)]
+pub struct MockChain {
+    activation_heights: ActivationHeights,
+    blocks: Vec<MockBlock>,
+    mempool: Vec<MockTransaction>,
+    registered_transactions: Vec<MockTransaction>,
+    submitted_transactions: Vec<String>,
+    utxos: Vec<MockUtxo>,
+}
+
+impl MockChain {
+    /// Creates an empty chain with the given network upgrade activation heights.
+    pub fn new(activation_heights: ActivationHeights) -> Self {
+        MockChain {
+            activation_heights,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the height of the chain tip or `None` if the chain is empty.
+    pub fn height(&self) -> Option<u32> {
+        (self.blocks.len() as u32).checked_sub(1)
+    }
+
+    /// Returns the blocks in the chain, indexed by height.
+    pub fn blocks(&self) -> &[MockBlock] {
+        &self.blocks
+    }
+
+    /// Appends `block` to the chain and returns its height.
+    ///
+    /// Transactions in the block are removed from the mempool.
+    pub fn push_block(&mut self, block: MockBlock) -> u32 {
+        self.mempool
+            .retain(|tx| !block.transactions.iter().any(|mined| mined.txid == tx.txid));
+        self.blocks.push(block);
+        self.height().expect("chain is not empty")
+    }
+
+    /// Appends `num_blocks` blocks with deterministic placeholder hashes and no transactions.
+    ///
+    /// The blocks have no serialized data, so this is only suitable for clients which do not parse blocks, unlike
+    /// indexers. Use [`MockChain::push_block`] with real serialized blocks for those.
+    pub fn generate_blocks(&mut self, num_blocks: u32) {
+        for _ in 0..num_blocks {
+            let height = self.blocks.len() as u64;
+            let block = MockBlock {
+                hash: format!("{:064x}", height + 1),
+                time: 1_700_000_000 + height as u32,
+                ..Default::default()
+            };
+            self.push_block(block);
+        }
+    }
+
+    /// Removes all blocks above `height`, returning their transactions to the mempool.
+    ///
+    /// Push replacement blocks afterwards to simulate a reorg.
+    pub fn truncate(&mut self, height: u32) {
+        let removed = self
+            .blocks
+            .split_off((height as usize + 1).min(self.blocks.len()));
+        self.mempool
+            .extend(removed.into_iter().flat_map(|block| block.transactions));
+        self.utxos.retain(|utxo| utxo.height <= height);
+    }
+
+    /// Adds `transaction` to the mempool.
+    pub fn add_mempool_transaction(&mut self, transaction: MockTransaction) {
+        self.mempool.push(transaction);
+    }
+
+    /// Returns the transactions in the mempool.
+    pub fn mempool(&self) -> &[MockTransaction] {
+        &self.mempool
+    }
+
+    /// Registers `transaction` so that it is accepted into the mempool when submitted with `sendrawtransaction`.
+    ///
+    /// The mock validator does not parse transactions, so it can only return the txid of registered transactions.
+    pub fn register_transaction(&mut self, transaction: MockTransaction) {
+        self.registered_transactions.push(transaction);
+    }
+
+    /// Returns the serialized transactions (hex) submitted with `sendrawtransaction`.
+    pub fn submitted_transactions(&self) -> &[String] {
+        &self.submitted_transactions
+    }
+
+    /// Adds a transparent output to be served by `getaddressutxos`.
+    pub fn add_utxo(&mut self, utxo: MockUtxo) {
+        self.utxos.push(utxo);
+    }
+
+    fn tip_height(&self) -> i64 {
+        self.blocks.len() as i64 - 1
+    }
+
+    fn block(&self, hash_or_height: &Value) -> Result<(u32, &MockBlock), (i64, String)> {
+        let height = match hash_or_height {
+            Value::Number(height) => height.as_u64(),
+            Value::String(hash_or_height) => match hash_or_height.parse::<u64>() {
+                Ok(height) if hash_or_height.len() < 64 => Some(height),
+                _ => self
+                    .blocks
+                    .iter()
+                    .position(|block| &block.hash == hash_or_height)
+                    .map(|height| height as u64),
+            },
+            _ => None,
+        };
+
+        height
+            .and_then(|height| {
+                self.blocks
+                    .get(height as usize)
+                    .map(|block| (height as u32, block))
+            })
+            .ok_or_else(|| {
+                (
+                    RPC_INVALID_ADDRESS_OR_KEY,
+                    format!("Block not found: {hash_or_height}"),
+                )
+            })
+    }
+
+    fn transaction(&self, txid: &str) -> Option<(&MockTransaction, Option<u32>)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .find_map(|(height, block)| {
+                block
+                    .transactions
+                    .iter()
+                    .find(|tx| tx.txid == txid)
+                    .map(|tx| (tx, Some(height as u32)))
+            })
+            .or_else(|| {
+                self.mempool
+                    .iter()
+                    .find(|tx| tx.txid == txid)
+                    .map(|tx| (tx, None))
+            })
+    }
+
+    fn dispatch(&mut self, method: &str, params: &[Value]) -> Result<Value, (i64, String)> {
+        let param = |index: usize| params.get(index).cloned().unwrap_or(Value::Null);
+        let tip = self.tip_height();
+
+        match method {
+            "getinfo" => Ok(json!({
+                "version": 5_000_000,
+                "build": "mock",
+                "subversion": "/MockValidator:0.1.0/",
+                "protocolversion": 170_100,
+                "blocks": tip,
+                "connections": 0,
+                "testnet": false,
+                "errors": "",
+            })),
+            "getblockchaininfo" => {
+                let next_height = (tip + 1) as u32;
+                let upgrades: serde_json::Map<String, Value> =
+                    network::network_upgrades(&self.activation_heights)
+                        .into_iter()
+                        .map(|(name, branch_id, activation_height)| {
+                            let status = if next_height > activation_height {
+                                "active"
+                            } else {
+                                "pending"
+                            };
+                            (
+                                branch_id.to_string(),
+                                json!({
+                                    "name": name,
+                                    "activationheight": activation_height,
+                                    "status": status,
+                                    "info": "",
+                                }),
+                            )
+                        })
+                        .collect();
+                Ok(json!({
+                    "chain": "regtest",
+                    "blocks": tip,
+                    "headers": tip,
+                    "bestblockhash": self.blocks.last().map(|block| block.hash.as_str()).unwrap_or_default(),
+                    "difficulty": 1.0,
+                    "verificationprogress": 1.0,
+                    "chainwork": "0",
+                    "pruned": false,
+                    "size_on_disk": 0,
+                    "estimatedheight": tip,
+                    "commitments": 0,
+                    "valuePools": [],
+                    "softforks": [],
+                    "upgrades": upgrades,
+                    "consensus": {
+                        "chaintip": network::consensus_branch_id(&self.activation_heights, tip.max(0) as u32),
+                        "nextblock": network::consensus_branch_id(&self.activation_heights, next_height),
+                    },
+                }))
+            }
+            "getblockcount" => Ok(json!(tip)),
+            "getbestblockhash" => self
+                .blocks
+                .last()
+                .map(|block| json!(block.hash))
+                .ok_or_else(|| (RPC_INVALID_ADDRESS_OR_KEY, "Chain is empty".to_string())),
+            "getblockhash" => self.block(&param(0)).map(|(_, block)| json!(block.hash)),
+            "getdifficulty" => Ok(json!(1.0)),
+            "getblock" => {
+                let (height, block) = self.block(&param(0))?;
+                let verbosity = param(1).as_u64().unwrap_or(1);
+                if verbosity == 0 {
+                    return Ok(json!(block.raw));
+                }
+                let tx: Vec<Value> = block
+                    .transactions
+                    .iter()
+                    .map(|tx| {
+                        if verbosity >= 2 {
+                            json!({ "txid": tx.txid, "hex": tx.raw })
+                        } else {
+                            json!(tx.txid)
+                        }
+                    })
+                    .collect();
+                let mut verbose_block = json!({
+                    "hash": block.hash,
+                    "confirmations": tip - height as i64 + 1,
+                    "size": block.raw.len() / 2,
+                    "height": height,
+                    "version": 4,
+                    "time": block.time,
+                    "tx": tx,
+                    "difficulty": 1.0,
+                    "trees": {
+                        "sapling": { "size": block.sapling_tree_size },
+                        "orchard": { "size": block.orchard_tree_size },
+                    },
+                });
+                if let Some(previous) = height.checked_sub(1) {
+                    verbose_block["previousblockhash"] = json!(self.blocks[previous as usize].hash);
+                }
+                if let Some(next) = self.blocks.get(height as usize + 1) {
+                    verbose_block["nextblockhash"] = json!(next.hash);
+                }
+                Ok(verbose_block)
+            }
+            "getrawtransaction" => {
+                let txid = param(0);
+                let (tx, height) = txid
+                    .as_str()
+                    .and_then(|txid| self.transaction(txid))
+                    .ok_or_else(|| {
+                        (
+                            RPC_INVALID_ADDRESS_OR_KEY,
+                            "No such mempool or blockchain transaction".to_string(),
+                        )
+                    })?;
+                let verbose = match param(1) {
+                    Value::Bool(verbose) => verbose,
+                    Value::Number(verbose) => verbose.as_u64().unwrap_or_default() != 0,
+                    _ => false,
+                };
+                if !verbose {
+                    return Ok(json!(tx.raw));
+                }
+                let mut verbose_tx = json!({ "hex": tx.raw, "txid": tx.txid });
+                if let Some(height) = height {
+                    verbose_tx["height"] = json!(height);
+                    verbose_tx["confirmations"] = json!(tip - height as i64 + 1);
+                    verbose_tx["blockhash"] = json!(self.blocks[height as usize].hash);
+                    verbose_tx["time"] = json!(self.blocks[height as usize].time);
+                }
+                Ok(verbose_tx)
+            }
+            "getrawmempool" => Ok(json!(self
+                .mempool
+                .iter()
+                .map(|tx| tx.txid.as_str())
+                .collect::<Vec<_>>())),
+            "sendrawtransaction" => {
+                let raw = param(0)
+                    .as_str()
+                    .ok_or_else(|| (RPC_INVALID_PARAMS, "Expected hex string".to_string()))?
+                    .to_string();
+                self.submitted_transactions.push(raw.clone());
+                let transaction = self
+                    .registered_transactions
+                    .iter()
+                    .find(|tx| tx.raw == raw)
+                    .cloned()
+                    .ok_or_else(|| {
+                        (
+                            RPC_VERIFY_REJECTED,
+                            "mock validator: transaction was not registered".to_string(),
+                        )
+                    })?;
+                if self.transaction(&transaction.txid).is_none() {
+                    self.mempool.push(transaction.clone());
+                }
+                Ok(json!(transaction.txid))
+            }
+            "z_gettreestate" => {
+                let (height, block) = self.block(&param(0))?;
+                Ok(json!({
+                    "hash": block.hash,
+                    "height": height,
+                    "time": block.time,
+                    "sapling": { "commitments": { "finalState": block.sapling_tree } },
+                    "orchard": { "commitments": { "finalState": block.orchard_tree } },
+                }))
+            }
+            "getaddressutxos" | "getaddresstxids" | "getaddressbalance" => {
+                let request = param(0);
+                let addresses: Vec<&str> = match &request {
+                    Value::String(address) => vec![address.as_str()],
+                    request => request["addresses"]
+                        .as_array()
+                        .map(|addresses| addresses.iter().filter_map(Value::as_str).collect())
+                        .unwrap_or_default(),
+                };
+                let start = request["start"].as_u64().unwrap_or(0) as u32;
+                let end = request["end"].as_u64().map_or(u32::MAX, |end| end as u32);
+                let utxos = self.utxos.iter().filter(|utxo| {
+                    addresses.contains(&utxo.address.as_str())
+                        && (start..=end).contains(&utxo.height)
+                });
+
+                match method {
+                    "getaddressutxos" => Ok(json!(utxos
+                        .map(|utxo| json!({
+                            "address": utxo.address,
+                            "txid": utxo.txid,
+                            "outputIndex": utxo.output_index,
+                            "script": utxo.script,
+                            "satoshis": utxo.satoshis,
+                            "height": utxo.height,
+                        }))
+                        .collect::<Vec<_>>())),
+                    "getaddresstxids" => {
+                        let mut seen = HashSet::new();
+                        let txids: Vec<&str> = utxos
+                            .map(|utxo| utxo.txid.as_str())
+                            .filter(|txid| seen.insert(*txid))
+                            .collect();
+                        Ok(json!(txids))
+                    }
+                    _ => {
+                        let balance: u64 = utxos.map(|utxo| utxo.satoshis).sum();
+                        Ok(json!({ "balance": balance, "received": balance }))
+                    }
+                }
+            }
+            "ping" => Ok(Value::Null),
+            method => Err((RPC_METHOD_NOT_FOUND, format!("Method not found: {method}"))),
+        }
+    }
+
+    fn handle_request(&mut self, request: &Value) -> (u16, Value) {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = request["params"].as_array().cloned().unwrap_or_default();
+
+        match self.dispatch(method, &params) {
+            Ok(result) => (200, json!({ "result": result, "error": null, "id": id })),
+            Err((code, message)) => {
+                let status = if code == RPC_METHOD_NOT_FOUND {
+                    404
+                } else {
+                    500
+                };
+                (
+                    status,
+                    json!({
+                        "result": null,
+                        "error": { "code": code, "message": message },
+                        "id": id,
+                    }),
+                )
+            }
+        }
+    }
+}
+
+/// This struct is used to represent and manage a mock validator.
+///
+/// The mock validator serves the subset of the zcashd JSON-RPC interface used by indexers from a [`MockChain`]
+/// held in memory, so it can be used in place of the Zcashd RPC port. Any RPC credentials are accepted. The server
+/// stops when dropped.
+///
+/// Indexers such as Zainod and Lightwalletd parse the blocks returned by `getblock`, so the chain must be scripted
+/// with real serialized blocks, e.g. captured from Zcashd, to launch them against the mock. The placeholder blocks of
+/// [`MockChain::generate_blocks`] are only suitable for clients which do not parse blocks.
+pub struct MockValidator {
+    port: Port,
+    chain: Arc<Mutex<MockChain>>,
+    _server: HttpServer,
+}
+
+impl MockValidator {
+    /// Launches the mock validator serving `chain` and returns [`crate::mock::MockValidator`].
+    ///
+    /// Use `rpc_port` to specify a port for the mock validator. Otherwise, a port is picked at random.
+    pub fn launch(rpc_port: Option<Port>, chain: MockChain) -> std::io::Result<MockValidator> {
+        let chain = Arc::new(Mutex::new(chain));
+
+        let server_chain = chain.clone();
+        let server = HttpServer::bind(rpc_port, move |request| {
+            let mut chain = server_chain
+                .lock()
+                .expect("mock chain lock should not be poisoned");
+            match serde_json::from_slice(&request.body) {
+                Ok(Value::Array(batch)) => {
+                    let responses: Vec<Value> = batch
+                        .iter()
+                        .map(|request| chain.handle_request(request).1)
+                        .collect();
+                    HttpResponse::json(200, &json!(responses))
+                }
+                Ok(request) => {
+                    let (status, response) = chain.handle_request(&request);
+                    HttpResponse::json(status, &response)
+                }
+                Err(e) => HttpResponse::json(
+                    400,
+                    &json!({
+                        "result": null,
+                        "error": { "code": -32700, "message": format!("Parse error: {e}") },
+                        "id": null,
+                    }),
+                ),
+            }
+        })?;
+
+        Ok(MockValidator {
+            port: server.port(),
+            chain,
+            _server: server,
+        })
+    }
+
+    /// Returns the RPC port.
+    pub fn port(&self) -> &Port {
+        &self.port
+    }
+
+    /// Locks and returns the chain for scripting.
+    ///
+    /// Example usage for mining a block:
+    /// ```ignore (incomplete)
+    /// mock_validator.chain().push_block(block);
+    /// ```
+    pub fn chain(&self) -> MutexGuard<'_, MockChain> {
+        self.chain
+            .lock()
+            .expect("mock chain lock should not be poisoned")
+    }
+}
+
+impl Default for MockValidator {
+    /// Default launch for the mock validator with a single empty genesis block.
+    /// Panics on failure.
+    fn default() -> Self {
+        let mut chain = MockChain::new(ActivationHeights::default());
+        chain.generate_blocks(1);
+        MockValidator::launch(None, chain).unwrap()
+    }
+}
+
+impl Validator for MockValidator {
+    fn rpc_port(&self) -> Port {
+        self.port
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        error::RpcError,
+        network::ActivationHeights,
+        rpc::{RpcClient, RpcCredentials},
+    };
+
+    use super::{MockBlock, MockChain, MockTransaction, MockUtxo, MockValidator};
+
+    #[test]
+    fn scripted_chain() {
+        let mut chain = MockChain::new(ActivationHeights::default());
+        chain.generate_blocks(2);
+        let mock_validator = MockValidator::launch(None, chain).unwrap();
+        let client = RpcClient::new(*mock_validator.port(), RpcCredentials::default());
+
+        assert_eq!(client.call("getblockcount", json!([])).unwrap(), json!(1));
+
+        let tx = MockTransaction {
+            txid: "ab".repeat(32),
+            raw: "0400008085202f89".to_string(),
+        };
+        mock_validator.chain().push_block(MockBlock {
+            hash: "cd".repeat(32),
+            raw: "00".to_string(),
+            transactions: vec![tx.clone()],
+            sapling_tree: "01".to_string(),
+            ..Default::default()
+        });
+        mock_validator.chain().add_utxo(MockUtxo {
+            address: "tmAddress".to_string(),
+            txid: tx.txid.clone(),
+            satoshis: 5_000,
+            height: 2,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            client.call("getbestblockhash", json!([])).unwrap(),
+            json!("cd".repeat(32))
+        );
+        let block = client.call("getblock", json!(["2", 1])).unwrap();
+        assert_eq!(block["tx"], json!([tx.txid]));
+        assert_eq!(block["previousblockhash"], json!(format!("{:064x}", 2)));
+        assert_eq!(
+            client
+                .call("getrawtransaction", json!([tx.txid, 0]))
+                .unwrap(),
+            json!(tx.raw)
+        );
+        assert_eq!(
+            client.call("z_gettreestate", json!(["2"])).unwrap()["sapling"]["commitments"]
+                ["finalState"],
+            json!("01")
+        );
+        assert_eq!(
+            client
+                .call("getaddressutxos", json!([{ "addresses": ["tmAddress"] }]))
+                .unwrap()[0]["satoshis"],
+            json!(5_000)
+        );
+        mock_validator.chain().add_utxo(MockUtxo {
+            address: "tmAddress".to_string(),
+            txid: "ef".repeat(32),
+            height: 2,
+            ..Default::default()
+        });
+        mock_validator.chain().add_utxo(MockUtxo {
+            address: "tmAddress".to_string(),
+            txid: tx.txid.clone(),
+            output_index: 1,
+            height: 2,
+            ..Default::default()
+        });
+        assert_eq!(
+            client
+                .call("getaddresstxids", json!([{ "addresses": ["tmAddress"] }]))
+                .unwrap(),
+            json!([tx.txid, "ef".repeat(32)])
+        );
+
+        mock_validator.chain().truncate(1);
+        assert_eq!(client.call("getblockcount", json!([])).unwrap(), json!(1));
+        assert_eq!(
+            client.call("getrawmempool", json!([])).unwrap(),
+            json!([tx.txid])
+        );
+        assert!(matches!(
+            client.call("getnewaddress", json!([])),
+            Err(RpcError::Rpc { code: -32601, .. })
+        ));
+    }
+}
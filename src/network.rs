@@ -33,6 +33,37 @@ impl Default for ActivationHeights {
     }
 }
 
+/// Returns the name, consensus branch id and activation height of each network upgrade, in activation order.
+pub(crate) fn network_upgrades(
+    activation_heights: &ActivationHeights,
+) -> [(&'static str, &'static str, u32); 6] {
+    [
+        (
+            "Overwinter",
+            "5ba81b19",
+            activation_heights.overwinter.into(),
+        ),
+        ("Sapling", "76b809bb", activation_heights.sapling.into()),
+        ("Blossom", "2bb40e60", activation_heights.blossom.into()),
+        ("Heartwood", "f5b9230b", activation_heights.heartwood.into()),
+        ("Canopy", "e9ff75a6", activation_heights.canopy.into()),
+        ("NU5", "c2d6d0b4", activation_heights.nu5.into()),
+    ]
+}
+
+/// Returns the consensus branch id active at `height`.
+pub(crate) fn consensus_branch_id(
+    activation_heights: &ActivationHeights,
+    height: u32,
+) -> &'static str {
+    network_upgrades(activation_heights)
+        .into_iter()
+        .rev()
+        .find(|(_, _, activation_height)| height >= *activation_height)
+        .map(|(_, branch_id, _)| branch_id)
+        .unwrap_or("00000000")
+}
+
 /// Checks `fixed_port` is not in use.
 /// If `fixed_port` is `None`, returns a random free port between 15_000 and 25_000.
 pub(crate) fn pick_unused_port(fixed_port: Option<Port>) -> Port {
@@ -0,0 +1,504 @@
+//! Async API for launching and controlling processes, enabled by the `async` feature
+//!
+//! The handles mirror [`crate::Zcashd`], [`crate::Zainod`] and [`crate::Lightwalletd`] but launch, wait and stop
+//! without blocking the calling thread, so they can be used from tokio runtimes. Processes are spawned with [`tokio::process::Command`], which
+//! requires a runtime with the IO and time drivers enabled, e.g. `#[tokio::test]`.
+//!
+//! Example usage:
+//! ```ignore (incomplete)
+//! let options = LaunchOptions::default();
+//! let zcashd = nonblocking::Zcashd::launch(None, None, None, &activation_heights, None, &options).await?;
+//! let zainod = nonblocking::Zainod::launch(None, None, *zcashd.port(), &options).await?;
+//! zcashd.generate_blocks(2).await?;
+//! zcashd.wait_for_height(2, Duration::from_secs(60)).await?;
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use getset::Getters;
+use portpicker::Port;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use tokio::process::Child;
+
+use crate::{
+    artifacts::ArtifactOptions,
+    config,
+    darkside::DarksideClient,
+    error::{DarksideError, LaunchError, RpcError, WaitError},
+    logs::ProcessLogs,
+    manifest::ManifestEntry,
+    network::ActivationHeights,
+    notify::{self, ChainEvent},
+    orphans::PidFile,
+    rpc::{RpcClient, RpcCredentials},
+    wait, zcash_cli, Indexer, LaunchOptions, Process,
+};
+
+/// This struct is used to represent and manage the Zcashd process from async code.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct Zcashd {
+    /// Child process handle
+    handle: Child,
+    /// Process id
+    pid: u32,
+    /// RPC Port
+    port: Port,
+    /// RPC credentials
+    credentials: RpcCredentials,
+    /// ZMQ notification port, `None` unless launched with [`crate::LaunchOptions::zmq_notifications`]
+    zmq_port: Option<Port>,
+    /// Data directory
+    _data_dir: TempDir,
+    /// Logs directory
+    logs_dir: TempDir,
+    /// Config directory
+    config_dir: TempDir,
+    /// Path to zcash cli binary
+    zcash_cli_bin: Option<PathBuf>,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
+}
+
+impl Zcashd {
+    /// Launches Zcashd process and returns [`crate::nonblocking::Zcashd`] once it is ready.
+    ///
+    /// See [`crate::Zcashd::launch`] and [`crate::Zcashd::launch_with_options`].
+    pub async fn launch(
+        zcashd_bin: Option<PathBuf>,
+        zcash_cli_bin: Option<PathBuf>,
+        rpc_port: Option<Port>,
+        activation_heights: &ActivationHeights,
+        miner_address: Option<&str>,
+        options: &LaunchOptions,
+    ) -> Result<Zcashd, LaunchError> {
+        let miner_address = miner_address.map(str::to_string);
+        let activation_heights = *activation_heights;
+        let prepare_options = options.clone();
+        let prepared = prepare(move || {
+            crate::Zcashd::prepare(
+                zcashd_bin,
+                zcash_cli_bin,
+                rpc_port,
+                &activation_heights,
+                miner_address.as_deref(),
+                &prepare_options,
+            )
+        })
+        .await?;
+        let launched = prepared
+            .launch
+            .launch_async(prepared.command, options)
+            .await?;
+
+        Ok(Zcashd {
+            pid: launched
+                .handle
+                .id()
+                .expect("process should not be awaited yet"),
+            handle: launched.handle,
+            port: prepared.launch.port,
+            credentials: RpcCredentials::default(),
+            zmq_port: prepared.zmq_port,
+            _data_dir: prepared.data_dir,
+            logs_dir: launched.logs_dir,
+            config_dir: prepared.launch.config_dir,
+            zcash_cli_bin: prepared.zcash_cli_bin,
+            artifacts: launched.artifacts,
+            _pid_file: launched.pid_file,
+            _manifest_entry: launched.manifest_entry,
+        })
+    }
+
+    /// Returns path to config file.
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.path().join(config::ZCASHD_FILENAME)
+    }
+
+    /// Returns a [`crate::rpc::RpcClient`] connected to the RPC port.
+    pub fn rpc_client(&self) -> RpcClient {
+        RpcClient::new(self.port, self.credentials.clone())
+    }
+
+    /// Calls the JSON-RPC `method` with `params` and returns the result.
+    pub async fn rpc_call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        self.rpc_client().call_async(method, params).await
+    }
+
+    /// Runs a Zcash-cli command with the given `args`.
+    ///
+    /// See [`crate::Zcashd::zcash_cli_command`].
+    pub async fn zcash_cli_command(&self, args: &[&str]) -> std::io::Result<std::process::Output> {
+        let command = zcash_cli(self.zcash_cli_bin.as_deref(), &self.config_path(), args);
+        tokio::process::Command::from(command).output().await
+    }
+
+    /// Generate `num_blocks` blocks.
+    pub async fn generate_blocks(&self, num_blocks: u32) -> std::io::Result<std::process::Output> {
+        self.zcash_cli_command(&["generate", &num_blocks.to_string()])
+            .await
+    }
+
+    /// Waits until the chain reaches `height`.
+    ///
+    /// See [`crate::Validator::wait_for_height`].
+    pub async fn wait_for_height(&self, height: u32, timeout: Duration) -> Result<(), WaitError> {
+        wait::for_height_async(&self.rpc_client(), height, timeout).await
+    }
+
+    /// Waits until the transaction with the given `txid` is in the mempool.
+    ///
+    /// See [`crate::Validator::wait_for_mempool_tx`].
+    pub async fn wait_for_mempool_tx(
+        &self,
+        txid: &str,
+        timeout: Duration,
+    ) -> Result<(), WaitError> {
+        wait::for_mempool_tx_async(&self.rpc_client(), txid, timeout).await
+    }
+
+    /// Waits until the transaction with the given `txid` has at least `confirmations` confirmations.
+    ///
+    /// See [`crate::Validator::wait_for_confirmations`].
+    pub async fn wait_for_confirmations(
+        &self,
+        txid: &str,
+        confirmations: u32,
+        timeout: Duration,
+    ) -> Result<(), WaitError> {
+        wait::for_confirmations_async(&self.rpc_client(), txid, confirmations, timeout).await
+    }
+
+    /// Subscribes to the block and transaction notifications published by Zcashd over ZMQ.
+    ///
+    /// See [`crate::Zcashd::subscribe`]. The receiver blocks, so receive on a blocking task or poll with
+    /// [`std::sync::mpsc::Receiver::try_recv`].
+    pub fn subscribe(&self) -> std::io::Result<Receiver<ChainEvent>> {
+        notify::subscribe(self.zmq_port)
+    }
+
+    /// Stops the Zcashd process.
+    ///
+    /// Requests a shutdown over RPC and waits for the process to exit, or kills it if the request fails.
+    pub async fn stop(&mut self) {
+        match self.rpc_call("stop", json!([])).await {
+            Ok(_) => {
+                if let Err(e) = self.handle.wait().await {
+                    tracing::error!("zcashd cannot be awaited: {e}")
+                } else {
+                    tracing::info!("zcashd successfully shut down")
+                };
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Can't stop zcashd over RPC: {e}\n\
+                    Sending SIGKILL to zcashd process."
+                );
+                if let Err(e) = self.handle.kill().await {
+                    tracing::warn!("zcashd has already terminated: {e}")
+                };
+            }
+        }
+    }
+}
+
+impl ProcessLogs for Zcashd {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", Process::Zcashd, self.pid)
+    }
+}
+
+impl Drop for Zcashd {
+    /// Kills the process unless it was stopped with [`Zcashd::stop`], as `Drop` cannot wait for a graceful shutdown.
+    fn drop(&mut self) {
+        kill_on_drop(&mut self.handle, Process::Zcashd);
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &Process::Zcashd.to_string(),
+                self.pid,
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                Some(self._data_dir.path()),
+            );
+        }
+    }
+}
+
+/// This struct is used to represent and manage the Zainod process from async code.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct Zainod {
+    /// Child process handle
+    handle: Child,
+    /// Process id
+    pid: u32,
+    /// RPC Port
+    port: Port,
+    /// Logs directory
+    logs_dir: TempDir,
+    /// Config directory
+    config_dir: TempDir,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
+}
+
+impl Zainod {
+    /// Launches Zainod process and returns [`crate::nonblocking::Zainod`] once it is ready.
+    ///
+    /// See [`crate::Zainod::launch`] and [`crate::Zainod::launch_with_options`].
+    pub async fn launch(
+        zainod_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        validator_port: Port,
+        options: &LaunchOptions,
+    ) -> Result<Zainod, LaunchError> {
+        let prepare_options = options.clone();
+        let (command, prepared) = prepare(move || {
+            crate::Zainod::prepare(zainod_bin, listen_port, validator_port, &prepare_options)
+        })
+        .await?;
+        let launched = prepared.launch_async(command, options).await?;
+
+        Ok(Zainod {
+            pid: launched
+                .handle
+                .id()
+                .expect("process should not be awaited yet"),
+            handle: launched.handle,
+            port: prepared.port,
+            logs_dir: launched.logs_dir,
+            config_dir: prepared.config_dir,
+            artifacts: launched.artifacts,
+            _pid_file: launched.pid_file,
+            _manifest_entry: launched.manifest_entry,
+        })
+    }
+
+    /// Returns path to config file.
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.path().join(config::ZAINOD_FILENAME)
+    }
+
+    /// Stops the Zainod process.
+    pub async fn stop(&mut self) {
+        if let Err(e) = self.handle.kill().await {
+            tracing::warn!("zainod has already terminated: {e}")
+        }
+    }
+}
+
+impl Indexer for Zainod {
+    fn listen_port(&self) -> Port {
+        self.port
+    }
+}
+
+impl ProcessLogs for Zainod {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", Process::Zainod, self.pid)
+    }
+}
+
+impl Drop for Zainod {
+    fn drop(&mut self) {
+        kill_on_drop(&mut self.handle, Process::Zainod);
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &Process::Zainod.to_string(),
+                self.pid,
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                None,
+            );
+        }
+    }
+}
+
+/// This struct is used to represent and manage the Lightwalletd process from async code.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct Lightwalletd {
+    /// Child process handle
+    handle: Child,
+    /// Process id
+    pid: u32,
+    /// gRPC Port
+    port: Port,
+    /// Data directory
+    _data_dir: TempDir,
+    /// Logs directory
+    logs_dir: TempDir,
+    /// Config directory
+    config_dir: TempDir,
+    /// Whether Lightwalletd was launched in darkside mode
+    darkside: bool,
+    /// Artifact options
+    #[getset(skip)]
+    artifacts: Option<ArtifactOptions>,
+    /// Pid file
+    #[getset(skip)]
+    _pid_file: PidFile,
+    /// Manifest entry
+    #[getset(skip)]
+    _manifest_entry: Option<ManifestEntry>,
+}
+
+impl Lightwalletd {
+    /// Launches Lightwalletd process and returns [`crate::nonblocking::Lightwalletd`] once it is ready.
+    ///
+    /// See [`crate::Lightwalletd::launch`] and [`crate::Lightwalletd::launch_with_options`].
+    pub async fn launch(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        zcashd_conf: PathBuf,
+        options: &LaunchOptions,
+    ) -> Result<Lightwalletd, LaunchError> {
+        Self::launch_mode(lightwalletd_bin, listen_port, Some(zcashd_conf), options).await
+    }
+
+    /// Launches Lightwalletd process in darkside mode and returns [`crate::nonblocking::Lightwalletd`] once it is
+    /// ready.
+    ///
+    /// See [`crate::Lightwalletd::launch_darkside`] and [`crate::Lightwalletd::launch_darkside_with_options`].
+    pub async fn launch_darkside(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        options: &LaunchOptions,
+    ) -> Result<Lightwalletd, LaunchError> {
+        Self::launch_mode(lightwalletd_bin, listen_port, None, options).await
+    }
+
+    async fn launch_mode(
+        lightwalletd_bin: Option<PathBuf>,
+        listen_port: Option<Port>,
+        zcashd_conf: Option<PathBuf>,
+        options: &LaunchOptions,
+    ) -> Result<Lightwalletd, LaunchError> {
+        let darkside = zcashd_conf.is_none();
+        let prepare_options = options.clone();
+        let (command, prepared, data_dir) = prepare(move || {
+            crate::Lightwalletd::prepare(
+                lightwalletd_bin,
+                listen_port,
+                zcashd_conf.as_deref(),
+                &prepare_options,
+            )
+        })
+        .await?;
+        let launched = prepared.launch_async(command, options).await?;
+
+        Ok(Lightwalletd {
+            pid: launched
+                .handle
+                .id()
+                .expect("process should not be awaited yet"),
+            handle: launched.handle,
+            port: prepared.port,
+            _data_dir: data_dir,
+            logs_dir: launched.logs_dir,
+            config_dir: prepared.config_dir,
+            darkside,
+            artifacts: launched.artifacts,
+            _pid_file: launched.pid_file,
+            _manifest_entry: launched.manifest_entry,
+        })
+    }
+
+    /// Returns path to config file.
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.path().join(config::LIGHTWALLETD_FILENAME)
+    }
+
+    /// Connects a [`crate::darkside::DarksideClient`] to the darkside service.
+    ///
+    /// See [`crate::Lightwalletd::darkside_client`].
+    pub async fn darkside_client(&self) -> Result<DarksideClient, DarksideError> {
+        if !self.darkside {
+            return Err(DarksideError::NotDarkside);
+        }
+        Ok(DarksideClient::connect(self.port).await?)
+    }
+
+    /// Stops the Lightwalletd process.
+    pub async fn stop(&mut self) {
+        if let Err(e) = self.handle.kill().await {
+            tracing::warn!("lightwalletd has already terminated: {e}")
+        }
+    }
+}
+
+impl Indexer for Lightwalletd {
+    fn listen_port(&self) -> Port {
+        self.port
+    }
+}
+
+impl ProcessLogs for Lightwalletd {
+    fn logs_path(&self) -> &Path {
+        self.logs_dir.path()
+    }
+
+    fn process_label(&self) -> String {
+        format!("{}[{}]", Process::Lightwalletd, self.pid)
+    }
+}
+
+impl Drop for Lightwalletd {
+    fn drop(&mut self) {
+        kill_on_drop(&mut self.handle, Process::Lightwalletd);
+        if let Some(artifacts) = &self.artifacts {
+            artifacts.preserve(
+                &Process::Lightwalletd.to_string(),
+                self.pid,
+                self.logs_dir.path(),
+                self.config_dir.path(),
+                Some(self._data_dir.path()),
+            );
+        }
+    }
+}
+
+/// Prepares a launch on the blocking thread pool, as it runs the binary to check its version and checks the params.
+async fn prepare<T: Send + 'static>(
+    prepare: impl FnOnce() -> Result<T, LaunchError> + Send + 'static,
+) -> Result<T, LaunchError> {
+    tokio::task::spawn_blocking(prepare)
+        .await
+        .expect("launch preparation should not panic")
+}
+
+/// Sends SIGKILL to the process if it is still running, without waiting for it to exit.
+fn kill_on_drop(handle: &mut Child, process: Process) {
+    if let Ok(None) = handle.try_wait() {
+        if let Err(e) = handle.start_kill() {
+            tracing::error!("{process} couldn't be killed: {e}");
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Module for subscribing to chain notifications published by validators over ZMQ
+//!
+//! Implements the subset of ZMTP 3.0 needed by a SUB socket with the NULL security mechanism.
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use portpicker::Port;
+
+const HASHBLOCK_TOPIC: &[u8] = b"hashblock";
+const HASHTX_TOPIC: &[u8] = b"hashtx";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// Chain event published by a validator.
+///
+/// Hashes and txids are hex encoded in RPC byte order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// Block connected to the chain tip
+    Block {
+        /// Block hash
+        hash: String,
+    },
+    /// Transaction added to the mempool or connected in a block
+    Transaction {
+        /// Transaction id
+        txid: String,
+    },
+}
+
+impl ChainEvent {
+    fn from_message(message: &[Vec<u8>]) -> Option<Self> {
+        let topic = message.first()?;
+        let hash = message
+            .get(1)?
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        match topic.as_slice() {
+            HASHBLOCK_TOPIC => Some(ChainEvent::Block { hash }),
+            HASHTX_TOPIC => Some(ChainEvent::Transaction { txid: hash }),
+            _ => None,
+        }
+    }
+}
+
+/// Subscribes to the `hashblock` and `hashtx` topics published on `zmq_port`, returning an error if `None`.
+///
+/// Returns once the subscription is sent. The publisher applies it asynchronously, so events published before it
+/// does are not received (the ZMQ "slow joiner" problem). The subscriber runs on its own thread, which exits when
+/// the receiver is dropped or the publisher disconnects.
+pub(crate) fn subscribe(zmq_port: Option<Port>) -> std::io::Result<Receiver<ChainEvent>> {
+    let zmq_port = zmq_port.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "zcashd does not publish ZMQ notifications",
+        )
+    })?;
+    let mut stream = TcpStream::connect_timeout(
+        &SocketAddr::from(([127, 0, 0, 1], zmq_port)),
+        CONNECT_TIMEOUT,
+    )?;
+    handshake(&mut stream, "SUB")?;
+    for topic in [HASHBLOCK_TOPIC, HASHTX_TOPIC] {
+        write_frame(&mut stream, 0, &[&[0x01], topic].concat())?;
+    }
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        while let Ok(message) = read_message(&mut stream) {
+            if let Some(event) = ChainEvent::from_message(&message) {
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(receiver)
+}
+
+/// Exchanges greetings and READY commands with the peer.
+fn handshake(stream: &mut TcpStream, socket_type: &str) -> std::io::Result<()> {
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting)?;
+
+    let mut peer_greeting = [0u8; 64];
+    stream.read_exact(&mut peer_greeting)?;
+    if peer_greeting[0] != 0xff || peer_greeting[9] != 0x7f || peer_greeting[10] < 3 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "peer does not support ZMTP 3",
+        ));
+    }
+
+    let mut ready = b"\x05READY\x0bSocket-Type".to_vec();
+    ready.extend((socket_type.len() as u32).to_be_bytes());
+    ready.extend(socket_type.as_bytes());
+    write_frame(stream, FLAG_COMMAND, &ready)?;
+
+    let (flags, _) = read_frame(stream)?;
+    if flags & FLAG_COMMAND == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "expected READY command from peer",
+        ));
+    }
+    Ok(())
+}
+
+fn write_frame(stream: &mut TcpStream, flags: u8, body: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+    if body.len() > u8::MAX as usize {
+        frame.push(flags | FLAG_LONG);
+        frame.extend((body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend(body);
+    stream.write_all(&frame)
+}
+
+fn read_frame(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut flags = [0u8; 1];
+    stream.read_exact(&mut flags)?;
+    let size = if flags[0] & FLAG_LONG == 0 {
+        let mut size = [0u8; 1];
+        stream.read_exact(&mut size)?;
+        size[0] as usize
+    } else {
+        let mut size = [0u8; 8];
+        stream.read_exact(&mut size)?;
+        u64::from_be_bytes(size) as usize
+    };
+    let mut body = vec![0u8; size];
+    stream.read_exact(&mut body)?;
+    Ok((flags[0], body))
+}
+
+/// Reads the frames of the next message, skipping commands.
+fn read_message(stream: &mut TcpStream) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut message = Vec::new();
+    loop {
+        let (flags, body) = read_frame(stream)?;
+        if flags & FLAG_COMMAND != 0 {
+            continue;
+        }
+        message.push(body);
+        if flags & FLAG_MORE == 0 {
+            return Ok(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, time::Duration};
+
+    use super::{ChainEvent, FLAG_MORE};
+
+    #[test]
+    fn subscribe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let publisher = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            super::handshake(&mut stream, "PUB").unwrap();
+            for topic in [super::HASHBLOCK_TOPIC, super::HASHTX_TOPIC] {
+                let subscription = super::read_message(&mut stream).unwrap();
+                assert_eq!(subscription, vec![[&[0x01], topic].concat()]);
+            }
+            for (topic, hash) in [(&b"hashblock"[..], [0xab; 32]), (b"hashtx", [0xcd; 32])] {
+                super::write_frame(&mut stream, FLAG_MORE, topic).unwrap();
+                super::write_frame(&mut stream, FLAG_MORE, &hash).unwrap();
+                super::write_frame(&mut stream, 0, &[0; 4]).unwrap();
+            }
+        });
+
+        let receiver = super::subscribe(Some(port)).unwrap();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            ChainEvent::Block {
+                hash: "ab".repeat(32)
+            }
+        );
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+            ChainEvent::Transaction {
+                txid: "cd".repeat(32)
+            }
+        );
+        publisher.join().unwrap();
+
+        assert_eq!(
+            super::subscribe(None).unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Module for protecting against processes orphaned by a crashed test process
+//!
+//! `Drop` does not run if the test process is killed or aborts, leaving its processes running and holding ports and
+//! temporary directories. Two mechanisms cover this:
+//! - On Linux, processes are spawned with `PR_SET_PDEATHSIG` so the kernel kills them when the test process exits.
+//!   The signal is sent when the spawning thread exits, so all processes are spawned from a dedicated thread which
+//!   runs until the test process exits, whichever thread launched them.
+//! - A pid file is written to the state directory for each running process and removed when its handle is dropped.
+//!   [`cleanup_stale`] kills the processes recorded by test processes which are no longer running.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{mpsc::Sender, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Environment variable specifying the state directory, defaults to `zcash_local_net` in the system temporary
+/// directory.
+pub const STATE_DIR_ENV: &str = "ZCASH_LOCAL_NET_STATE_DIR";
+
+const PIDS_DIR: &str = "pids";
+
+type SpawnJob = Box<dyn FnOnce() + Send>;
+
+/// Sender of jobs to the spawner thread, which is never joined
+static SPAWNER: Mutex<Option<Sender<SpawnJob>>> = Mutex::new(None);
+
+/// Process left running by a test process which is no longer running.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleProcess {
+    /// Process name
+    pub process_name: String,
+    /// Process id
+    pub pid: u32,
+    /// Whether the process was still running and has been killed
+    pub killed: bool,
+}
+
+/// Record of a running process written to its pid file
+#[derive(Debug, Serialize, Deserialize)]
+struct PidRecord {
+    process_name: String,
+    pid: u32,
+    /// Start time of the process, to avoid killing an unrelated process that reused the pid
+    start_time: Option<u64>,
+    parent_pid: u32,
+    parent_start_time: Option<u64>,
+    /// Temporary directories of the process
+    dirs: Vec<PathBuf>,
+}
+
+/// Pid file of a running process, removed when dropped.
+///
+/// Failures are logged as the pid file only matters after a crash.
+pub(crate) struct PidFile {
+    path: Option<PathBuf>,
+    record: PidRecord,
+}
+
+impl PidFile {
+    fn create(pids_dir: &Path, process_name: &str, pid: u32) -> Self {
+        let parent_pid = std::process::id();
+        let mut pid_file = PidFile {
+            path: None,
+            record: PidRecord {
+                process_name: process_name.to_string(),
+                pid,
+                start_time: start_time(pid),
+                parent_pid,
+                parent_start_time: start_time(parent_pid),
+                dirs: Vec::new(),
+            },
+        };
+        match std::fs::create_dir_all(pids_dir) {
+            Ok(()) => {
+                pid_file.path = Some(pids_dir.join(format!("{pid}.json")));
+                pid_file.write();
+            }
+            Err(e) => tracing::warn!("failed to create {}: {e}", pids_dir.display()),
+        }
+        pid_file
+    }
+
+    /// Records the temporary directories of the process, removed by [`cleanup_stale`].
+    pub(crate) fn set_dirs(&mut self, dirs: &[&Path]) {
+        self.record.dirs = dirs.iter().map(|dir| dir.to_path_buf()).collect();
+        self.write();
+    }
+
+    fn write(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let record = serde_json::to_string(&self.record).expect("should serialize pid record");
+        if let Err(e) = std::fs::write(path, record) {
+            tracing::warn!("failed to write pid file {}: {e}", path.display());
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            if let Err(e) = std::fs::remove_file(path) {
+                tracing::warn!("failed to remove pid file {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Returns the state directory, see [`STATE_DIR_ENV`].
+pub fn state_dir() -> PathBuf {
+    std::env::var_os(STATE_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("zcash_local_net"))
+}
+
+/// Spawns `command` and writes its pid file.
+///
+/// Unless `allow_orphans` is set, the process is killed when the test process exits on Linux.
+pub(crate) fn spawn(
+    mut command: Command,
+    process_name: &str,
+    allow_orphans: bool,
+) -> std::io::Result<(Child, PidFile)> {
+    let child = if allow_orphans {
+        command.spawn()?
+    } else {
+        kill_on_parent_death(&mut command);
+        on_spawner_thread(move || command.spawn())?
+    };
+    let pid_file = PidFile::create(&state_dir().join(PIDS_DIR), process_name, child.id());
+    Ok((child, pid_file))
+}
+
+/// Async version of [`spawn`], spawning `command` with [`tokio::process::Command`].
+///
+/// Must be called from within a tokio runtime, which the process is registered with.
+#[cfg(feature = "async")]
+pub(crate) fn spawn_async(
+    command: Command,
+    process_name: &str,
+    allow_orphans: bool,
+) -> std::io::Result<(tokio::process::Child, PidFile)> {
+    let mut command = tokio::process::Command::from(command);
+    let child = if allow_orphans {
+        command.spawn()?
+    } else {
+        kill_on_parent_death(command.as_std_mut());
+        let runtime = tokio::runtime::Handle::current();
+        on_spawner_thread(move || {
+            let _runtime = runtime.enter();
+            command.spawn()
+        })?
+    };
+    let pid = child
+        .id()
+        .expect("process should not be awaited before returning");
+    let pid_file = PidFile::create(&state_dir().join(PIDS_DIR), process_name, pid);
+    Ok((child, pid_file))
+}
+
+/// Runs `spawn` on the spawner thread, started on first use, and returns its result.
+fn on_spawner_thread<T: Send + 'static>(spawn: impl FnOnce() -> T + Send + 'static) -> T {
+    let (result_sender, result_receiver) = std::sync::mpsc::channel();
+    let job: SpawnJob = Box::new(move || {
+        // the receiver is only dropped if the calling thread panicked
+        let _ = result_sender.send(spawn());
+    });
+
+    SPAWNER
+        .lock()
+        .expect("spawner lock should not be poisoned")
+        .get_or_insert_with(|| {
+            let (sender, receiver) = std::sync::mpsc::channel::<SpawnJob>();
+            std::thread::Builder::new()
+                .name("zcash_local_net spawner".to_string())
+                .spawn(move || {
+                    for job in receiver {
+                        job();
+                    }
+                })
+                .expect("should be able to spawn spawner thread");
+            sender
+        })
+        .send(job)
+        .expect("spawner thread should be running");
+    result_receiver
+        .recv()
+        .expect("spawner thread should return the spawn result")
+}
+
+/// Sets `PR_SET_PDEATHSIG` so the process is killed when the spawning thread exits. Linux only.
+fn kill_on_parent_death(command: &mut Command) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::process::CommandExt;
+
+        let parent_pid = std::process::id() as libc::pid_t;
+        // SAFETY: only async-signal-safe functions are called between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // the parent may have exited before the death signal was set
+                if libc::getppid() != parent_pid {
+                    libc::_exit(1);
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = command;
+}
+
+/// Kills the processes left running by test processes which are no longer running, e.g. after being killed with
+/// SIGKILL, and removes their pid files and temporary directories.
+///
+/// Processes launched by running test processes are left untouched, so this is safe to call at the start of each
+/// test run.
+pub fn cleanup_stale() -> std::io::Result<Vec<StaleProcess>> {
+    cleanup_pids_dir(&state_dir().join(PIDS_DIR))
+}
+
+fn cleanup_pids_dir(pids_dir: &Path) -> std::io::Result<Vec<StaleProcess>> {
+    let entries = match std::fs::read_dir(pids_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut stale = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let record: PidRecord = match std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|record| serde_json::from_str(&record).ok())
+        {
+            Some(record) => record,
+            None => {
+                // partially written or not a pid file
+                tracing::warn!("removing invalid pid file {}", path.display());
+                std::fs::remove_file(&path)?;
+                continue;
+            }
+        };
+        if is_running(record.parent_pid, record.parent_start_time) {
+            continue;
+        }
+
+        let killed = is_running(record.pid, record.start_time) && kill(record.pid);
+        if killed {
+            tracing::info!(
+                "killed stale {} process {}",
+                record.process_name,
+                record.pid
+            );
+        }
+        for dir in &record.dirs {
+            if let Err(e) = std::fs::remove_dir_all(dir) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("failed to remove {}: {e}", dir.display());
+                }
+            }
+        }
+        std::fs::remove_file(&path)?;
+        stale.push(StaleProcess {
+            process_name: record.process_name,
+            pid: record.pid,
+            killed,
+        });
+    }
+    Ok(stale)
+}
+
+/// Returns the start time of a process in clock ticks since boot, if available.
+///
+/// Recorded with a pid, the start time tells the process apart from a later process reusing the pid, see
+/// [`is_running`]. Only available on Linux.
+#[cfg(target_os = "linux")]
+pub fn start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the process name may contain spaces, so fields are counted from the closing parenthesis, which follows field 2
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(19)?
+        .parse()
+        .ok()
+}
+
+/// Returns the start time of a process in clock ticks since boot, if available.
+///
+/// Recorded with a pid, the start time tells the process apart from a later process reusing the pid, see
+/// [`is_running`]. Only available on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Returns whether the process is running and, if `expected_start_time` is known, was not replaced by another
+/// process reusing the pid.
+///
+/// `expected_start_time` is the [`start_time`] recorded when the process was launched. Always returns `false` on
+/// platforms other than unix.
+pub fn is_running(pid: u32, expected_start_time: Option<u64>) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: signal 0 only checks that the process exists
+        let exists = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0
+            || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+        exists && (expected_start_time.is_none() || start_time(pid) == expected_start_time)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (pid, expected_start_time);
+        false
+    }
+}
+
+fn kill(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: sending a signal has no memory safety requirements
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process::{Command, Stdio},
+        time::Duration,
+    };
+
+    use super::{PidFile, PidRecord};
+
+    #[test]
+    fn cleanup_stale() {
+        let pids_dir = tempfile::tempdir().unwrap();
+        let mut child = Command::new("sleep")
+            .arg("60")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut pid_file = PidFile::create(pids_dir.path(), "sleep", child.id());
+        let pid_path = pids_dir.path().join(format!("{}.json", child.id()));
+        assert!(pid_path.exists());
+
+        // processes of running test processes are left untouched
+        assert!(super::cleanup_pids_dir(pids_dir.path()).unwrap().is_empty());
+        assert!(child.try_wait().unwrap().is_none());
+
+        // simulate a crashed test process
+        let temp_dir = tempfile::tempdir().unwrap();
+        let leftover_dir = temp_dir.path().join("leftover");
+        std::fs::create_dir(&leftover_dir).unwrap();
+        pid_file.set_dirs(&[&leftover_dir]);
+        std::mem::forget(pid_file);
+        let mut record: PidRecord =
+            serde_json::from_str(&std::fs::read_to_string(&pid_path).unwrap()).unwrap();
+        // above the maximum pid
+        record.parent_pid = i32::MAX as u32;
+        std::fs::write(&pid_path, serde_json::to_string(&record).unwrap()).unwrap();
+
+        let stale = super::cleanup_pids_dir(pids_dir.path()).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].process_name, "sleep");
+        assert!(stale[0].killed);
+        assert!(!child.wait().unwrap().success());
+        assert!(!pid_path.exists());
+        assert!(!leftover_dir.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn spawned_processes_outlive_spawning_thread() {
+        let (mut child, _pid_file) = std::thread::spawn(|| {
+            let mut command = Command::new("sleep");
+            command.arg("60").stdout(Stdio::null());
+            super::spawn(command, "sleep", false).unwrap()
+        })
+        .join()
+        .unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(child.try_wait().unwrap().is_none());
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
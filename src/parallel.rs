@@ -0,0 +1,190 @@
+//! Module for launching independent processes concurrently
+//!
+//! Each launch waits for its process to be ready, so launching several processes one after another adds up their
+//! startup times. [`launch_all`] runs the launches concurrently and waits for all of them.
+
+use crate::error::{LaunchError, ParallelLaunchError};
+
+/// Runs the `launches` concurrently and returns the launched processes in the same order.
+///
+/// If any launch fails, the processes which launched successfully are dropped, stopping them, and all launch errors
+/// are returned. If any launch panics, the panic is resumed after the other launches finished and were torn down.
+///
+/// Example usage for launching validators and then their indexers concurrently:
+/// ```ignore (incomplete)
+/// let validators = launch_all((0..3).map(|_| || Zcashd::launch(None, None, None, &activation_heights, None)))?;
+/// let indexers = launch_all(validators.iter().map(|validator| {
+///     let validator_port = *validator.port();
+///     move || Zainod::launch(None, None, validator_port)
+/// }))?;
+/// ```
+pub fn launch_all<T, F, I>(launches: I) -> Result<Vec<T>, ParallelLaunchError>
+where
+    T: Send,
+    F: FnOnce() -> Result<T, LaunchError> + Send,
+    I: IntoIterator<Item = F>,
+{
+    // launch threads are named after the calling thread, which names the artifacts folder of the processes
+    let thread_name = std::thread::current().name().map(str::to_string);
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let launches: Vec<_> = launches
+            .into_iter()
+            .map(|launch| {
+                let mut builder = std::thread::Builder::new();
+                if let Some(thread_name) = &thread_name {
+                    builder = builder.name(thread_name.clone());
+                }
+                builder
+                    .spawn_scoped(scope, launch)
+                    .expect("should be able to spawn launch thread")
+            })
+            .collect();
+        launches.into_iter().map(|launch| launch.join()).collect()
+    });
+    let total = results.len();
+
+    let mut launched = Vec::with_capacity(total);
+    let mut failures = Vec::new();
+    let mut panic = None;
+    for (index, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(Ok(process)) => launched.push(process),
+            Ok(Err(error)) => failures.push((index, error)),
+            Err(payload) => panic = Some(payload),
+        }
+    }
+
+    if failures.is_empty() && panic.is_none() {
+        return Ok(launched);
+    }
+    tear_down(launched);
+    if let Some(payload) = panic {
+        std::panic::resume_unwind(payload);
+    }
+    Err(ParallelLaunchError { total, failures })
+}
+
+/// Drops the processes concurrently, as stopping a process may wait for it to shut down.
+fn tear_down<T: Send>(processes: Vec<T>) {
+    std::thread::scope(|scope| {
+        for process in processes {
+            scope.spawn(move || drop(process));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        error::LaunchError,
+        local_process::{LocalProcess, LocalProcessSpec},
+        matcher::Matcher,
+        LaunchOptions,
+    };
+
+    struct Process(Arc<AtomicUsize>);
+
+    impl Drop for Process {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn launch_all() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let processes = super::launch_all((0..3).map(|_| {
+            let dropped = dropped.clone();
+            move || {
+                let start = Instant::now();
+                std::thread::sleep(Duration::from_millis(200));
+                Ok((Process(dropped), start, Instant::now()))
+            }
+        }))
+        .unwrap();
+        assert_eq!(processes.len(), 3);
+        // the launches ran concurrently if every launch started before any launch ended
+        let last_start = processes.iter().map(|(_, start, _)| *start).max().unwrap();
+        let first_end = processes.iter().map(|(_, _, end)| *end).min().unwrap();
+        assert!(last_start < first_end);
+        drop(processes);
+
+        dropped.store(0, Ordering::SeqCst);
+        let error = super::launch_all((0..4).map(|index| {
+            let dropped = dropped.clone();
+            move || {
+                if index % 2 == 1 {
+                    return Err(LaunchError::Setup {
+                        process_name: format!("process {index}"),
+                        message: "failed".to_string(),
+                    });
+                }
+                Ok(Process(dropped))
+            }
+        }))
+        .err()
+        .unwrap();
+        assert_eq!(error.total, 4);
+        assert_eq!(
+            error
+                .failures
+                .iter()
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+
+        let thread_names = super::launch_all(
+            (0..2).map(|_| || Ok(std::thread::current().name().map(str::to_string))),
+        )
+        .unwrap();
+        assert_eq!(
+            thread_names,
+            vec![std::thread::current().name().map(str::to_string); 2]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn launched_processes_outlive_launch() {
+        let processes = super::launch_all((0..2).map(|_| {
+            || {
+                LocalProcess::launch(
+                    LocalProcessSpec {
+                        name: "sidecar".to_string(),
+                        command: PathBuf::from("sh"),
+                        args: vec!["-c".to_string(), "echo ready; sleep 60".to_string()],
+                        write_config: None,
+                        ports: Vec::new(),
+                        ready_matcher: Matcher::contains("ready"),
+                        failure_matcher: None,
+                    },
+                    &LaunchOptions::default(),
+                )
+            }
+        }))
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        for process in processes {
+            let stat =
+                std::fs::read_to_string(format!("/proc/{}/stat", process.handle().id())).unwrap();
+            // the process state follows the process name in parentheses
+            assert!(!stat
+                .rsplit_once(')')
+                .unwrap()
+                .1
+                .trim_start()
+                .starts_with('Z'));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Module for checking the Zcash proving parameters required by Zcashd
+//!
+//! Zcashd exits during startup if the Sprout and Sapling parameters are missing from its params directory. Checking
+//! them before launching reports the missing or corrupted files instead of a generic launch failure.
+//!
+//! [`check`] only compares the file sizes, which is cheap enough to run before every launch. [`verify`] also compares
+//! the checksums, which takes seconds.
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::LaunchError;
+
+/// Parameters file required by Zcashd
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParamsFile {
+    /// File name
+    pub name: &'static str,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex encoded SHA-256 checksum
+    pub sha256: &'static str,
+}
+
+/// Parameters files required by Zcashd
+pub const PARAMS_FILES: [ParamsFile; 3] = [
+    ParamsFile {
+        name: "sapling-spend.params",
+        size: 47958396,
+        sha256: "8e48ffd23abb3a5fd9c5589204f32d9c31285a04b78096ba40a79b75677efc13",
+    },
+    ParamsFile {
+        name: "sapling-output.params",
+        size: 3592860,
+        sha256: "2f0ebbcbb9bb0bcffe95a397e7eba89c29eb4dde6191c339db88570e3f3fb0e4",
+    },
+    ParamsFile {
+        name: "sprout-groth16.params",
+        size: 725523612,
+        sha256: "b685d700c60328498fbde589c8c7c484c722b788b265b72af448a5bf0ee55b50",
+    },
+];
+
+/// Files verified by this process, identified by path, size and modification time, as hashing them takes seconds.
+///
+/// The lock is held while hashing, so concurrent verifications of the same file hash it once.
+static VERIFIED: Mutex<Vec<(PathBuf, u64, SystemTime)>> = Mutex::new(Vec::new());
+
+/// Returns the params directory used by Zcashd if `-paramsdir` is not specified, or `None` if the home directory is
+/// unknown.
+pub fn default_params_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| {
+            PathBuf::from(home)
+                .join("Library")
+                .join("Application Support")
+                .join("ZcashParams")
+        })
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA").map(|app_data| PathBuf::from(app_data).join("ZcashParams"))
+    } else {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".zcash-params"))
+    }
+}
+
+/// Checks that `params_dir`, or the [`default_params_dir`] if `None`, contains the [`PARAMS_FILES`] with the
+/// expected sizes.
+///
+/// Returns [`LaunchError::MissingParams`] listing the files which are absent or have the wrong size. Use [`verify`]
+/// to also compare the checksums.
+pub fn check(params_dir: Option<&Path>) -> Result<(), LaunchError> {
+    check_files(&resolve_dir(params_dir)?, &PARAMS_FILES, false)
+}
+
+/// Checks that `params_dir`, or the [`default_params_dir`] if `None`, contains the [`PARAMS_FILES`] with the
+/// expected sizes and checksums.
+///
+/// Returns [`LaunchError::MissingParams`] listing the files which are absent, have the wrong size or fail the
+/// checksum. Verified files are not hashed again by this process unless they are modified.
+pub fn verify(params_dir: Option<&Path>) -> Result<(), LaunchError> {
+    check_files(&resolve_dir(params_dir)?, &PARAMS_FILES, true)
+}
+
+/// Returns `params_dir`, or the [`default_params_dir`] if `None`.
+fn resolve_dir(params_dir: Option<&Path>) -> Result<PathBuf, LaunchError> {
+    match params_dir {
+        Some(params_dir) => Ok(params_dir.to_path_buf()),
+        None => default_params_dir().ok_or_else(|| LaunchError::MissingParams {
+            params_dir: "unknown home directory".to_string(),
+            missing: PARAMS_FILES
+                .iter()
+                .map(|file| PathBuf::from(file.name))
+                .collect(),
+            invalid: Vec::new(),
+        }),
+    }
+}
+
+fn check_files(
+    params_dir: &Path,
+    files: &[ParamsFile],
+    verify_checksums: bool,
+) -> Result<(), LaunchError> {
+    let mut missing = Vec::new();
+    let mut invalid = Vec::new();
+    for file in files {
+        let path = params_dir.join(file.name);
+        let Ok(metadata) = path.metadata() else {
+            missing.push(path);
+            continue;
+        };
+        if metadata.len() != file.size {
+            tracing::warn!(
+                "{} has size {}, expected {}",
+                path.display(),
+                metadata.len(),
+                file.size
+            );
+            invalid.push(path);
+            continue;
+        }
+        if !verify_checksums {
+            continue;
+        }
+
+        let key = metadata
+            .modified()
+            .ok()
+            .map(|modified| (path.clone(), metadata.len(), modified));
+        let mut verified = VERIFIED
+            .lock()
+            .expect("verified lock should not be poisoned");
+        if key.as_ref().is_some_and(|key| verified.contains(key)) {
+            continue;
+        }
+
+        match sha256(&path) {
+            Ok(checksum) if checksum == file.sha256 => verified.extend(key),
+            Ok(checksum) => {
+                tracing::warn!(
+                    "{} has checksum {checksum}, expected {}",
+                    path.display(),
+                    file.sha256
+                );
+                invalid.push(path);
+            }
+            Err(e) => {
+                tracing::warn!("failed to read {}: {e}", path.display());
+                invalid.push(path);
+            }
+        }
+    }
+
+    if missing.is_empty() && invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(LaunchError::MissingParams {
+            params_dir: params_dir.display().to_string(),
+            missing,
+            invalid,
+        })
+    }
+}
+
+/// Returns the hex encoded SHA-256 checksum of the file at `path`.
+fn sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::LaunchError;
+
+    use super::ParamsFile;
+
+    #[test]
+    fn check_files() {
+        let params_dir = tempfile::tempdir().unwrap();
+        let files = [
+            ParamsFile {
+                name: "a.params",
+                size: 3,
+                // SHA-256 of "abc"
+                sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            },
+            ParamsFile {
+                name: "b.params",
+                size: 3,
+                sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            },
+        ];
+
+        match super::check_files(params_dir.path(), &files, false) {
+            Err(LaunchError::MissingParams {
+                missing, invalid, ..
+            }) => {
+                assert_eq!(missing.len(), 2);
+                assert!(invalid.is_empty());
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        std::fs::write(params_dir.path().join("a.params"), "abc").unwrap();
+        std::fs::write(params_dir.path().join("b.params"), "abcd").unwrap();
+        match super::check_files(params_dir.path(), &files, false) {
+            Err(LaunchError::MissingParams {
+                missing, invalid, ..
+            }) => {
+                assert!(missing.is_empty());
+                assert_eq!(invalid, vec![params_dir.path().join("b.params")]);
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        // the size matches, only the checksum differs
+        std::fs::write(params_dir.path().join("b.params"), "abd").unwrap();
+        super::check_files(params_dir.path(), &files, false).unwrap();
+        match super::check_files(params_dir.path(), &files, true) {
+            Err(LaunchError::MissingParams {
+                missing, invalid, ..
+            }) => {
+                assert!(missing.is_empty());
+                assert_eq!(invalid, vec![params_dir.path().join("b.params")]);
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        std::fs::write(params_dir.path().join("b.params"), "abc").unwrap();
+        super::check_files(params_dir.path(), &files, true).unwrap();
+    }
+}
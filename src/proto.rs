@@ -0,0 +1,5 @@
+//! Types and gRPC services generated from the lightwalletd protocol definitions in `proto/`
+#![allow(missing_docs)]
+#![allow(clippy::all)]
+
+tonic::include_proto!("cash.z.wallet.sdk.rpc");
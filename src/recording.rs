@@ -0,0 +1,278 @@
+//! Recording and replaying of validator JSON-RPC traffic
+//!
+//! [`RpcRecorder`] is a proxy placed between an indexer and the validator which records every request/response
+//! pair to a JSON lines file. [`RpcReplayer`] serves the responses from a recording, so that indexer behaviour can
+//! be reproduced without launching a validator.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use getset::Getters;
+use portpicker::Port;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::http::{self, HttpResponse, HttpServer};
+
+/// Suggested file name for RPC recordings, e.g. in a directory kept after the test.
+pub const RPC_RECORDING_LOG: &str = "rpc_recording.jsonl";
+
+/// A single recorded JSON-RPC request/response pair.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RpcExchange {
+    /// JSON-RPC request body
+    pub request: Value,
+    /// HTTP status of the response
+    pub status: u16,
+    /// JSON-RPC response body. `Null` if the response body was not valid JSON.
+    pub response: Value,
+}
+
+impl RpcExchange {
+    /// Reads all exchanges from a JSON lines recording.
+    pub fn read_recording(recording_path: &Path) -> std::io::Result<Vec<RpcExchange>> {
+        BufReader::new(File::open(recording_path)?)
+            .lines()
+            .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+            .map(|line| {
+                serde_json::from_str(&line?)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            })
+            .collect()
+    }
+}
+
+/// This struct is used to represent and manage a JSON-RPC recording proxy.
+///
+/// Point the indexer at [`RpcRecorder::port`] instead of the validator's RPC port. Requests are forwarded to the
+/// validator and each exchange is appended to the recording file. The proxy stops when dropped.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct RpcRecorder {
+    /// Proxy listen port
+    port: Port,
+    /// Port of the validator requests are forwarded to
+    validator_port: Port,
+    /// Path to the JSON lines recording
+    recording_path: PathBuf,
+    /// HTTP server
+    #[getset(skip)]
+    _server: HttpServer,
+}
+
+impl RpcRecorder {
+    /// Launches a recording proxy forwarding to `validator_port` and returns [`crate::recording::RpcRecorder`].
+    ///
+    /// Use `listen_port` to specify a port for the proxy. Otherwise, a port is picked at random.
+    ///
+    /// Exchanges are appended to the file at `recording_path`, which is created if it does not exist.
+    pub fn launch(
+        validator_port: Port,
+        listen_port: Option<Port>,
+        recording_path: PathBuf,
+    ) -> std::io::Result<RpcRecorder> {
+        let recording = Arc::new(Mutex::new(
+            File::options()
+                .create(true)
+                .append(true)
+                .open(&recording_path)?,
+        ));
+
+        let server = HttpServer::bind(listen_port, move |request| {
+            let response = match http::post(validator_port, &request.headers, &request.body) {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::error!("failed to forward request to validator: {e}");
+                    return HttpResponse::json(
+                        502,
+                        &json!({
+                            "result": null,
+                            "error": { "code": -32603, "message": format!("recording proxy: {e}") },
+                            "id": null,
+                        }),
+                    );
+                }
+            };
+
+            let exchange = RpcExchange {
+                request: serde_json::from_slice(&request.body).unwrap_or(Value::Null),
+                status: response.status,
+                response: serde_json::from_slice(&response.body).unwrap_or(Value::Null),
+            };
+            let mut recording = recording
+                .lock()
+                .expect("recording lock should not be poisoned");
+            if let Err(e) = writeln!(recording, "{}", json!(exchange)) {
+                tracing::error!("failed to write rpc recording: {e}");
+            }
+
+            response
+        })?;
+
+        Ok(RpcRecorder {
+            port: server.port(),
+            validator_port,
+            recording_path,
+            _server: server,
+        })
+    }
+
+    /// Reads all exchanges recorded so far.
+    pub fn exchanges(&self) -> std::io::Result<Vec<RpcExchange>> {
+        RpcExchange::read_recording(&self.recording_path)
+    }
+}
+
+/// This struct is used to represent and manage a JSON-RPC server replaying a recording.
+///
+/// Requests are matched against the recording by method and params, ignoring the request `id`. Repeated requests
+/// are answered with the recorded responses in order, repeating the last one once the recording is exhausted.
+/// Requests without a recorded response are answered with a JSON-RPC error. The server stops when dropped.
+#[derive(Getters)]
+#[getset(get = "pub")]
+pub struct RpcReplayer {
+    /// RPC Port
+    port: Port,
+    /// HTTP server
+    #[getset(skip)]
+    _server: HttpServer,
+}
+
+impl RpcReplayer {
+    /// Launches a JSON-RPC server replaying the recording at `recording_path` and returns
+    /// [`crate::recording::RpcReplayer`].
+    ///
+    /// Use `rpc_port` to specify a port for the server. Otherwise, a port is picked at random.
+    pub fn launch(recording_path: &Path, rpc_port: Option<Port>) -> std::io::Result<RpcReplayer> {
+        let mut responses: HashMap<String, VecDeque<(u16, Value)>> = HashMap::new();
+        for exchange in RpcExchange::read_recording(recording_path)? {
+            responses
+                .entry(request_key(&exchange.request))
+                .or_default()
+                .push_back((exchange.status, exchange.response));
+        }
+        let responses = Mutex::new(responses);
+
+        let server = HttpServer::bind(rpc_port, move |request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap_or(Value::Null);
+
+            let mut responses = responses
+                .lock()
+                .expect("replay lock should not be poisoned");
+            let Some(recorded) = responses.get_mut(&request_key(&request)) else {
+                tracing::warn!("no recorded response for request: {request}");
+                return HttpResponse::json(
+                    500,
+                    &json!({
+                        "result": null,
+                        "error": { "code": -32601, "message": "no recorded response for request" },
+                        "id": request.get("id").cloned().unwrap_or(Value::Null),
+                    }),
+                );
+            };
+            let (status, mut response) = if recorded.len() > 1 {
+                recorded.pop_front().expect("checked non-empty")
+            } else {
+                recorded
+                    .front()
+                    .cloned()
+                    .expect("recorded responses are never empty")
+            };
+
+            with_request_ids(&mut response, &request);
+            HttpResponse::json(status, &response)
+        })?;
+
+        Ok(RpcReplayer {
+            port: server.port(),
+            _server: server,
+        })
+    }
+}
+
+/// Returns a key identifying a request (or batch of requests) by method and params.
+fn request_key(request: &Value) -> String {
+    fn strip(request: &Value) -> Value {
+        json!({
+            "method": request.get("method").cloned().unwrap_or(Value::Null),
+            "params": request.get("params").cloned().unwrap_or(Value::Null),
+        })
+    }
+
+    match request {
+        Value::Array(batch) => Value::Array(batch.iter().map(strip).collect()).to_string(),
+        request => strip(request).to_string(),
+    }
+}
+
+/// Replaces the ids of a recorded `response` with the ids of the replayed `request`.
+fn with_request_ids(response: &mut Value, request: &Value) {
+    match (response, request) {
+        (Value::Array(responses), Value::Array(requests)) => {
+            for (response, request) in responses.iter_mut().zip(requests) {
+                with_request_ids(response, request);
+            }
+        }
+        (Value::Object(response), request) => {
+            if let Some(id) = request.get("id") {
+                response.insert("id".to_string(), id.clone());
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::http::{self, HttpResponse, HttpServer};
+
+    use super::{RpcExchange, RpcRecorder, RpcReplayer};
+
+    fn call(port: u16, request: &Value) -> Value {
+        let response = http::post(port, &[], request.to_string().as_bytes()).unwrap();
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let validator = HttpServer::bind(None, |request| {
+            let request: Value = serde_json::from_slice(&request.body).unwrap();
+            HttpResponse::json(
+                200,
+                &json!({ "result": 42, "error": null, "id": request["id"] }),
+            )
+        })
+        .unwrap();
+        let logs_dir = tempfile::tempdir().unwrap();
+        let recording_path = logs_dir.path().join(super::RPC_RECORDING_LOG);
+
+        let recorder = RpcRecorder::launch(validator.port(), None, recording_path.clone()).unwrap();
+        let request = json!({ "jsonrpc": "1.0", "id": 1, "method": "getblockcount", "params": [] });
+        assert_eq!(call(*recorder.port(), &request)["result"], 42);
+        assert_eq!(
+            recorder.exchanges().unwrap(),
+            vec![RpcExchange {
+                request: request.clone(),
+                status: 200,
+                response: json!({ "result": 42, "error": null, "id": 1 }),
+            }]
+        );
+        drop(recorder);
+        drop(validator);
+
+        let replayer = RpcReplayer::launch(&recording_path, None).unwrap();
+        let request = json!({ "jsonrpc": "1.0", "id": 7, "method": "getblockcount", "params": [] });
+        assert_eq!(
+            call(*replayer.port(), &request),
+            json!({ "result": 42, "error": null, "id": 7 })
+        );
+        let request =
+            json!({ "jsonrpc": "1.0", "id": 8, "method": "getbestblockhash", "params": [] });
+        assert_eq!(call(*replayer.port(), &request)["error"]["code"], -32601);
+    }
+}
@@ -0,0 +1,115 @@
+//! JSON-RPC client for validator RPC servers
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use base64::Engine;
+use portpicker::Port;
+use serde_json::{json, Value};
+
+use crate::{error::RpcError, http};
+
+/// Credentials for the validator RPC server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcCredentials {
+    /// RPC username
+    pub user: String,
+    /// RPC password
+    pub password: String,
+}
+
+impl Default for RpcCredentials {
+    /// Credentials written to the config files of validators launched by this crate.
+    fn default() -> Self {
+        Self {
+            user: "xxxxxx".to_string(),
+            password: "xxxxxx".to_string(),
+        }
+    }
+}
+
+/// JSON-RPC client connected to a validator RPC server on localhost.
+#[derive(Debug)]
+pub struct RpcClient {
+    port: Port,
+    credentials: RpcCredentials,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    /// Creates a client for the RPC server listening on `port`.
+    pub fn new(port: Port, credentials: RpcCredentials) -> Self {
+        RpcClient {
+            port,
+            credentials,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the RPC port.
+    pub fn port(&self) -> Port {
+        self.port
+    }
+
+    /// Calls `method` with `params` and returns the JSON-RPC result.
+    ///
+    /// Example usage:
+    /// ```ignore (incomplete)
+    /// let height = client.call("getblockcount", json!([]))?;
+    /// ```
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let (headers, request) = self.request(method, params);
+        let response = http::post(self.port, &headers, &request)?;
+        parse_response(response)
+    }
+
+    /// Async version of [`RpcClient::call`].
+    #[cfg(feature = "async")]
+    pub async fn call_async(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let (headers, request) = self.request(method, params);
+        let response = http::post_async(self.port, &headers, &request).await?;
+        parse_response(response)
+    }
+
+    /// Returns the headers and body of a request.
+    fn request(&self, method: &str, params: Value) -> (Vec<(String, String)>, Vec<u8>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({
+            "jsonrpc": "1.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        let authorization = base64::engine::general_purpose::STANDARD.encode(format!(
+            "{}:{}",
+            self.credentials.user, self.credentials.password
+        ));
+
+        (
+            vec![(
+                "Authorization".to_string(),
+                format!("Basic {authorization}"),
+            )],
+            request.to_string().into_bytes(),
+        )
+    }
+}
+
+/// Returns the JSON-RPC result of `response`.
+fn parse_response(response: http::HttpResponse) -> Result<Value, RpcError> {
+    let invalid_response = || RpcError::InvalidResponse {
+        status: response.status,
+        body: String::from_utf8_lossy(&response.body).to_string(),
+    };
+    let mut body: Value = serde_json::from_slice(&response.body).map_err(|_| invalid_response())?;
+    if !body.is_object() {
+        return Err(invalid_response());
+    }
+
+    match body.get("error") {
+        None | Some(Value::Null) => Ok(body["result"].take()),
+        Some(error) => Err(RpcError::Rpc {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+        }),
+    }
+}
@@ -0,0 +1,416 @@
+//! Module for polling process state until a condition is met
+
+use std::time::{Duration, Instant};
+
+use portpicker::Port;
+use serde_json::{json, Value};
+
+use crate::{client::IndexerClient, error::WaitError, rpc::RpcClient};
+
+const INITIAL_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Exponential backoff between polls, bounded by a timeout.
+pub(crate) struct Backoff {
+    deadline: Instant,
+    interval: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Backoff {
+            deadline: Instant::now() + timeout,
+            interval: INITIAL_INTERVAL,
+        }
+    }
+
+    /// Sleeps until the next poll.
+    /// Returns `false` without sleeping if the timeout has elapsed.
+    pub(crate) fn wait(&mut self) -> bool {
+        match self.next_interval() {
+            Some(interval) => {
+                std::thread::sleep(interval);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Async version of [`Backoff::wait`].
+    #[cfg(feature = "async")]
+    pub(crate) async fn wait_async(&mut self) -> bool {
+        match self.next_interval() {
+            Some(interval) => {
+                tokio::time::sleep(interval).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the time until the next poll, or `None` if the timeout has elapsed.
+    fn next_interval(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return None;
+        }
+        let interval = self.interval.min(self.deadline - now);
+        self.interval = (self.interval * 2).min(MAX_INTERVAL);
+        Some(interval)
+    }
+}
+
+/// Polls the indexer listening on `indexer_port` until its latest block matches the validator's best block.
+///
+/// Errors connecting to or querying either process are retried until the timeout, as the indexer may still be
+/// starting up.
+pub(crate) fn until_synced(
+    indexer_port: Port,
+    validator: &RpcClient,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    // the gRPC client is driven from a separate thread so this can be called from async tests
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("should be able to build runtime");
+                let mut backoff = Backoff::new(timeout);
+                let mut client = None;
+                let mut indexer_height = None;
+                let mut validator_height = None;
+                loop {
+                    let validator_tip = validator
+                        .call("getblockcount", json!([]))
+                        .ok()
+                        .and_then(|height| height.as_u64())
+                        .zip(
+                            validator
+                                .call("getbestblockhash", json!([]))
+                                .ok()
+                                .and_then(|hash| hash.as_str().map(str::to_string)),
+                        );
+                    if client.is_none() {
+                        client = runtime.block_on(IndexerClient::connect(indexer_port)).ok();
+                    }
+                    let indexer_tip = client
+                        .as_mut()
+                        .and_then(|client| runtime.block_on(client.get_latest_block()).ok());
+
+                    if let Some((height, _)) = validator_tip {
+                        validator_height = Some(height);
+                    }
+                    if let Some(tip) = &indexer_tip {
+                        indexer_height = Some(tip.height);
+                    }
+                    if let (Some((height, hash)), Some(tip)) = (validator_tip, indexer_tip) {
+                        if tip.height == height
+                            && (tip.hash.is_empty() || hash_matches(&tip.hash, &hash))
+                        {
+                            return Ok(());
+                        }
+                    }
+
+                    if !backoff.wait() {
+                        return Err(WaitError::SyncTimeout {
+                            indexer_height,
+                            validator_height,
+                        });
+                    }
+                }
+            })
+            .join()
+            .expect("sync wait thread should not panic")
+    })
+}
+
+/// Polls the validator's `getblockcount` until the chain reaches `height`.
+pub(crate) fn for_height(
+    validator: &RpcClient,
+    height: u32,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let mut backoff = Backoff::new(timeout);
+    let mut validator_height = None;
+    loop {
+        if let Some(current_height) = validator
+            .call("getblockcount", json!([]))
+            .ok()
+            .and_then(|height| height.as_u64())
+        {
+            if current_height >= height as u64 {
+                return Ok(());
+            }
+            validator_height = Some(current_height as u32);
+        }
+
+        if !backoff.wait() {
+            return Err(WaitError::HeightTimeout {
+                target: height,
+                height: validator_height,
+            });
+        }
+    }
+}
+
+/// Async version of [`for_height`].
+#[cfg(feature = "async")]
+pub(crate) async fn for_height_async(
+    validator: &RpcClient,
+    height: u32,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let mut backoff = Backoff::new(timeout);
+    let mut validator_height = None;
+    loop {
+        if let Some(current_height) = validator
+            .call_async("getblockcount", json!([]))
+            .await
+            .ok()
+            .and_then(|height| height.as_u64())
+        {
+            if current_height >= height as u64 {
+                return Ok(());
+            }
+            validator_height = Some(current_height as u32);
+        }
+
+        if !backoff.wait_async().await {
+            return Err(WaitError::HeightTimeout {
+                target: height,
+                height: validator_height,
+            });
+        }
+    }
+}
+
+/// Polls the validator's `getrawmempool` until it contains `txid`.
+pub(crate) fn for_mempool_tx(
+    validator: &RpcClient,
+    txid: &str,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let mut backoff = Backoff::new(timeout);
+    loop {
+        if let Ok(Value::Array(mempool)) = validator.call("getrawmempool", json!([])) {
+            if mempool.iter().any(|mempool_txid| mempool_txid == txid) {
+                return Ok(());
+            }
+        }
+
+        if !backoff.wait() {
+            return Err(WaitError::MempoolTimeout {
+                txid: txid.to_string(),
+            });
+        }
+    }
+}
+
+/// Async version of [`for_mempool_tx`].
+#[cfg(feature = "async")]
+pub(crate) async fn for_mempool_tx_async(
+    validator: &RpcClient,
+    txid: &str,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let mut backoff = Backoff::new(timeout);
+    loop {
+        if let Ok(Value::Array(mempool)) = validator.call_async("getrawmempool", json!([])).await {
+            if mempool.iter().any(|mempool_txid| mempool_txid == txid) {
+                return Ok(());
+            }
+        }
+
+        if !backoff.wait_async().await {
+            return Err(WaitError::MempoolTimeout {
+                txid: txid.to_string(),
+            });
+        }
+    }
+}
+
+/// Polls the validator's `getrawtransaction` until `txid` has at least `confirmations` confirmations.
+pub(crate) fn for_confirmations(
+    validator: &RpcClient,
+    txid: &str,
+    confirmations: u32,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let mut backoff = Backoff::new(timeout);
+    let mut current_confirmations = None;
+    loop {
+        // transactions in the mempool have no confirmations field
+        if let Some(confirmations_so_far) = validator
+            .call("getrawtransaction", json!([txid, 1]))
+            .ok()
+            .and_then(|transaction| transaction["confirmations"].as_u64())
+        {
+            if confirmations_so_far >= confirmations as u64 {
+                return Ok(());
+            }
+            current_confirmations = Some(confirmations_so_far as u32);
+        }
+
+        if !backoff.wait() {
+            return Err(WaitError::ConfirmationsTimeout {
+                txid: txid.to_string(),
+                target: confirmations,
+                confirmations: current_confirmations,
+            });
+        }
+    }
+}
+
+/// Async version of [`for_confirmations`].
+#[cfg(feature = "async")]
+pub(crate) async fn for_confirmations_async(
+    validator: &RpcClient,
+    txid: &str,
+    confirmations: u32,
+    timeout: Duration,
+) -> Result<(), WaitError> {
+    let mut backoff = Backoff::new(timeout);
+    let mut current_confirmations = None;
+    loop {
+        // transactions in the mempool have no confirmations field
+        if let Some(confirmations_so_far) = validator
+            .call_async("getrawtransaction", json!([txid, 1]))
+            .await
+            .ok()
+            .and_then(|transaction| transaction["confirmations"].as_u64())
+        {
+            if confirmations_so_far >= confirmations as u64 {
+                return Ok(());
+            }
+            current_confirmations = Some(confirmations_so_far as u32);
+        }
+
+        if !backoff.wait_async().await {
+            return Err(WaitError::ConfirmationsTimeout {
+                txid: txid.to_string(),
+                target: confirmations,
+                confirmations: current_confirmations,
+            });
+        }
+    }
+}
+
+/// Compares a block hash from the indexer with the validator's hex encoded block hash.
+///
+/// Indexers differ in whether the hash is returned in RPC (display) byte order or internal byte order, so both are
+/// accepted.
+fn hash_matches(indexer_hash: &[u8], validator_hash: &str) -> bool {
+    let hex = |bytes: &mut dyn Iterator<Item = &u8>| {
+        bytes.map(|byte| format!("{byte:02x}")).collect::<String>()
+    };
+    hex(&mut indexer_hash.iter()) == validator_hash
+        || hex(&mut indexer_hash.iter().rev()) == validator_hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        error::WaitError,
+        mock::{MockBlock, MockIndexer, MockTransaction, MockValidator},
+        proto::CompactBlock,
+        Indexer, Validator,
+    };
+
+    #[test]
+    fn wait_until_synced() {
+        let mock_validator = MockValidator::default();
+        let mock_indexer = MockIndexer::default();
+
+        match mock_indexer.wait_until_synced(&mock_validator, Duration::from_millis(100)) {
+            Err(WaitError::SyncTimeout {
+                indexer_height: None,
+                validator_height: Some(0),
+            }) => (),
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        mock_validator.chain().generate_blocks(2);
+        for height in 0..=2 {
+            let mut hash = vec![0; 32];
+            hash[0] = height as u8 + 1;
+            mock_indexer.chain().push_block(CompactBlock {
+                height,
+                hash,
+                ..Default::default()
+            });
+        }
+        mock_indexer
+            .wait_until_synced(&mock_validator, Duration::from_secs(5))
+            .unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn wait_for_height_async() {
+        let mock_validator = MockValidator::default();
+        let rpc_client = mock_validator.rpc_client();
+
+        match super::for_height_async(&rpc_client, 2, Duration::from_millis(100)).await {
+            Err(WaitError::HeightTimeout {
+                target: 2,
+                height: Some(0),
+            }) => (),
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        mock_validator.chain().generate_blocks(2);
+        super::for_height_async(&rpc_client, 2, Duration::from_secs(5))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn validator_waits() {
+        let mock_validator = MockValidator::default();
+        let timeout = Duration::from_millis(100);
+        let tx = MockTransaction {
+            txid: "ab".repeat(32),
+            raw: "00".to_string(),
+        };
+
+        match mock_validator.wait_for_height(2, timeout) {
+            Err(WaitError::HeightTimeout {
+                target: 2,
+                height: Some(0),
+            }) => (),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert!(matches!(
+            mock_validator.wait_for_mempool_tx(&tx.txid, timeout),
+            Err(WaitError::MempoolTimeout { .. })
+        ));
+
+        mock_validator.chain().add_mempool_transaction(tx.clone());
+        mock_validator
+            .wait_for_mempool_tx(&tx.txid, timeout)
+            .unwrap();
+        match mock_validator.wait_for_confirmations(&tx.txid, 2, timeout) {
+            Err(WaitError::ConfirmationsTimeout {
+                target: 2,
+                confirmations: None,
+                ..
+            }) => (),
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        mock_validator.chain().push_block(MockBlock {
+            hash: "cd".repeat(32),
+            transactions: vec![tx.clone()],
+            ..Default::default()
+        });
+        mock_validator.chain().generate_blocks(1);
+        mock_validator.wait_for_height(2, timeout).unwrap();
+        mock_validator
+            .wait_for_confirmations(&tx.txid, 2, timeout)
+            .unwrap();
+    }
+}
@@ -1,3 +1,10 @@
+use std::time::Duration;
+
+use zcash_local_net::{
+    logs::{ProcessLogs, Regex},
+    Indexer, Validator,
+};
+
 #[test]
 fn launch_zcashd() {
     tracing_subscriber::fmt().init();
@@ -6,12 +13,223 @@ fn launch_zcashd() {
     zcashd.print_stdout();
 }
 
+#[test]
+fn zcashd_wait_for_height() {
+    tracing_subscriber::fmt().init();
+
+    let zcashd = zcash_local_net::Zcashd::default();
+    zcashd.generate_blocks(2).unwrap();
+    zcashd.wait_for_height(2, Duration::from_secs(60)).unwrap();
+}
+
+#[test]
+fn zcashd_wait_for_log_line() {
+    tracing_subscriber::fmt().init();
+
+    let zcashd = zcash_local_net::Zcashd::default();
+    zcashd.generate_blocks(2).unwrap();
+    zcashd
+        .wait_for_log_line(
+            &Regex::new(r"UpdateTip: new best=\w+ height=2").unwrap(),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+    zcashd.print_stderr();
+}
+
+#[test]
+fn launch_zcashd_trace_logs() {
+    tracing_subscriber::fmt().init();
+
+    let options = zcash_local_net::LaunchOptions {
+        trace_logs: true,
+        ..Default::default()
+    };
+    let zcashd = zcash_local_net::Zcashd::launch_with_options(
+        None,
+        None,
+        None,
+        &zcash_local_net::network::ActivationHeights::default(),
+        None,
+        &options,
+    )
+    .unwrap();
+    zcash_local_net::Zainod::launch_with_options(None, None, *zcashd.port(), &options).unwrap();
+}
+
+#[test]
+fn zcashd_chain_events() {
+    tracing_subscriber::fmt().init();
+
+    let options = zcash_local_net::LaunchOptions {
+        zmq_notifications: true,
+        ..Default::default()
+    };
+    let zcashd = zcash_local_net::Zcashd::launch_with_options(
+        None,
+        None,
+        None,
+        &zcash_local_net::network::ActivationHeights::default(),
+        None,
+        &options,
+    )
+    .unwrap();
+    let events = zcashd.subscribe().unwrap();
+    // blocks generated before zcashd applies the subscription are missed, so generate until one is received
+    for _ in 0..10 {
+        zcashd.generate_blocks(1).unwrap();
+        // the coinbase transaction may be published before the block
+        while let Ok(event) = events.recv_timeout(Duration::from_secs(5)) {
+            if matches!(event, zcash_local_net::notify::ChainEvent::Block { .. }) {
+                return;
+            }
+        }
+    }
+    panic!("no block event received");
+}
+
 #[test]
 fn launch_zainod() {
     tracing_subscriber::fmt().init();
 
     let zcashd = zcash_local_net::Zcashd::default();
-    let zainod = zcash_local_net::Zainod::launch(None, None, zcashd.port().clone()).unwrap();
+    let zainod = zcash_local_net::Zainod::launch(None, None, *zcashd.port()).unwrap();
     zcashd.print_stdout();
     zainod.print_stdout();
 }
+
+#[test]
+fn zainod_wait_until_synced() {
+    tracing_subscriber::fmt().init();
+
+    let zcashd = zcash_local_net::Zcashd::default();
+    let zainod = zcash_local_net::Zainod::launch(None, None, *zcashd.port()).unwrap();
+    zcashd.generate_blocks(2).unwrap();
+    zainod
+        .wait_until_synced(&zcashd, Duration::from_secs(60))
+        .unwrap();
+}
+
+#[test]
+fn zcashd_zainod_timeline() {
+    tracing_subscriber::fmt().init();
+
+    let zcashd = zcash_local_net::Zcashd::default();
+    let zainod = zcash_local_net::Zainod::launch(None, None, *zcashd.port()).unwrap();
+    zcashd.generate_blocks(1).unwrap();
+    zainod
+        .wait_until_synced(&zcashd, Duration::from_secs(60))
+        .unwrap();
+    let timeline =
+        zcash_local_net::logs::write_timeline(&[&zcashd, &zainod], zcashd.logs_dir().path())
+            .unwrap();
+    assert!(timeline.contains("zcashd["));
+    assert!(timeline.contains("zainod["));
+    println!("{timeline}");
+}
+
+#[tokio::test]
+async fn record_and_replay_zainod_rpc() {
+    tracing_subscriber::fmt().init();
+
+    // the recording is kept outside the Zcashd directories, which are removed when Zcashd is dropped
+    let recording_dir = tempfile::tempdir().unwrap();
+    let zcashd = zcash_local_net::Zcashd::default();
+    let recorder = zcashd
+        .record_rpc(
+            None,
+            recording_dir
+                .path()
+                .join(zcash_local_net::recording::RPC_RECORDING_LOG),
+        )
+        .unwrap();
+    zcashd.generate_blocks(2).unwrap();
+    let zainod = zcash_local_net::Zainod::launch(None, None, *recorder.port()).unwrap();
+    zainod
+        .wait_until_synced(&zcashd, Duration::from_secs(60))
+        .unwrap();
+    drop(zainod);
+    drop(zcashd);
+
+    let replayer =
+        zcash_local_net::recording::RpcReplayer::launch(recorder.recording_path(), None).unwrap();
+    let zainod = zcash_local_net::Zainod::launch(None, None, *replayer.port()).unwrap();
+    let mut client = zainod.client().await.unwrap();
+    let start = std::time::Instant::now();
+    while client.get_latest_block().await.unwrap().height < 2 {
+        assert!(
+            start.elapsed() < Duration::from_secs(60),
+            "replayed zainod did not sync"
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    zainod.print_stdout();
+}
+
+#[tokio::test]
+async fn launch_lightwalletd() {
+    tracing_subscriber::fmt().init();
+
+    let zcashd = zcash_local_net::Zcashd::default();
+    let lightwalletd =
+        zcash_local_net::Lightwalletd::launch(None, None, zcashd.config_path()).unwrap();
+    assert!(matches!(
+        lightwalletd.darkside_client().await,
+        Err(zcash_local_net::error::DarksideError::NotDarkside)
+    ));
+    zcashd.print_stdout();
+    lightwalletd.print_stdout();
+}
+
+#[tokio::test]
+async fn launch_lightwalletd_darkside() {
+    tracing_subscriber::fmt().init();
+
+    let lightwalletd = zcash_local_net::Lightwalletd::launch_darkside(None, None).unwrap();
+    let mut darkside = lightwalletd.darkside_client().await.unwrap();
+    darkside.reset(1, "c2d6d0b4", "regtest").await.unwrap();
+    darkside.stage_empty_blocks(1, 0, 10).await.unwrap();
+    darkside.apply_staged(10).await.unwrap();
+    let mut client = lightwalletd.client().await.unwrap();
+    assert_eq!(client.get_latest_block().await.unwrap().height, 10);
+    lightwalletd.print_stdout();
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn launch_async() {
+    tracing_subscriber::fmt().init();
+
+    let options = zcash_local_net::LaunchOptions::default();
+    let mut zcashd = zcash_local_net::nonblocking::Zcashd::launch(
+        None,
+        None,
+        None,
+        &zcash_local_net::network::ActivationHeights::default(),
+        None,
+        &options,
+    )
+    .await
+    .unwrap();
+    let zainod = zcash_local_net::nonblocking::Zainod::launch(None, None, *zcashd.port(), &options)
+        .await
+        .unwrap();
+    zcashd.generate_blocks(2).await.unwrap();
+    zcashd
+        .wait_for_height(2, Duration::from_secs(60))
+        .await
+        .unwrap();
+    let mut client = zainod.client().await.unwrap();
+    client.get_latest_block().await.unwrap();
+    let lightwalletd = zcash_local_net::nonblocking::Lightwalletd::launch(
+        None,
+        None,
+        zcashd.config_path(),
+        &options,
+    )
+    .await
+    .unwrap();
+    let mut client = lightwalletd.client().await.unwrap();
+    assert_eq!(client.get_latest_block().await.unwrap().height, 2);
+    zcashd.stop().await;
+}